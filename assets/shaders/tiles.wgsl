@@ -1,49 +1,114 @@
 #import bevy_pbr::forward_io::VertexOutput;
+#import bevy_sprite::mesh2d_view_bindings::globals;
 // we can import items from shader modules in the assets folder with a quoted path
 // #import "shaders/custom_material_import.wgsl"::COLOR_MULTIPLIER
 
 // @group(1) @binding(0) var<uniform> material: ChunkMaterial;
 @group(1) @binding(1) var base_color_texture: texture_2d<f32>;
 @group(1) @binding(2) var base_color_sampler: sampler;
+// The tiles of each layer, bottom to top.
 @group(1) @binding(3) var<storage> tiles: array<u32>;
-@group(1) @binding(4) var<uniform> tile_size: u32;
+struct TileAtlas {
+  tile_size: u32,
+  margin: u32,
+  spacing: u32,
+  columns: u32,
+}
+
+@group(1) @binding(4) var<uniform> atlas: TileAtlas;
+@group(1) @binding(5) var<storage> animations: array<vec2<u32>>;
+@group(1) @binding(6) var<storage> frames: array<vec2<u32>>;
+@group(1) @binding(7) var<uniform> chunk_tiles_sq: u32;
 
 const TILE_OFFSET = 0.0;
+// Tiles of the layers above the ground that are left empty.
+const EMPTY_TILE = 0xffffffffu;
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
-  var d = textureDimensions(base_color_texture);
-  var len = arrayLength(&tiles);
-  var dim = sqrt(f32(len));
+  var dim = f32(chunk_tiles_sq);
+  var len = chunk_tiles_sq * chunk_tiles_sq;
+  var layers = arrayLength(&tiles) / len;
+  var offset = coords_to_tile_offset(mesh.uv, dim);
 
-  var idx = coords_to_tile_index(mesh.uv, dim);
-  var min = index_to_texture_coords(idx);
-  var max = min + vec2(f32(tile_size) - TILE_OFFSET);
+  var color = vec4(0.0);
+  for (var layer = 0u; layer < layers; layer++) {
+    var idx = tiles[layer * len + offset];
+    if idx == EMPTY_TILE {
+      continue;
+    }
 
-  var px = (mesh.uv.x % (1.0 / dim)) * dim;
-  var py = (mesh.uv.y % (1.0 / dim)) * dim;
+    var texel = sample_tile(animate(idx, mesh.uv, dim), mesh.uv, dim);
+    color = vec4(mix(color.rgb, texel.rgb, texel.a), max(color.a, texel.a));
+  }
+
+  return color;
+}
+
+/// Samples the given tile index at the position of the mesh uv within its tile.
+fn sample_tile(i: u32, uv: vec2<f32>, dim: f32) -> vec4<f32> {
+  var d = textureDimensions(base_color_texture);
+  var min = index_to_texture_coords(i);
+  var max = min + vec2(f32(atlas.tile_size) - TILE_OFFSET);
+
+  var px = (uv.x % (1.0 / dim)) * dim;
+  var py = (uv.y % (1.0 / dim)) * dim;
 
   var x = mix(f32(min.x), f32(max.x), px) / f32(d.x);
   var y = mix(f32(min.y), f32(max.y), py) / f32(d.y);
-  
-  return textureSample(base_color_texture, base_color_sampler, vec2(x, y));
+
+  // Sampled with an explicit level since layers are sampled in non-uniform control flow.
+  return textureSampleLevel(base_color_texture, base_color_sampler, vec2(x, y), 0.0);
 }
 
-/// Gets the [0..1] texture coords for the given tile index
+/// Gets the texture coords in pixels for the given tile index
 fn index_to_texture_coords(i: u32) -> vec2<f32> {
   var d = textureDimensions(base_color_texture);
-  var z = i * tile_size;
-  var x = clamp(z % d.x            , 0u, d.x - tile_size);
-  var y = clamp(z / d.x * tile_size, 0u, d.y - tile_size);
+  var stride = atlas.tile_size + atlas.spacing;
+  var x = clamp(atlas.margin + i % atlas.columns * stride, 0u, d.x - atlas.tile_size);
+  var y = clamp(atlas.margin + i / atlas.columns * stride, 0u, d.y - atlas.tile_size);
 
   return vec2(f32(x) + TILE_OFFSET, f32(y) + TILE_OFFSET);
 }
 
-/// Gets the index in the tiles array for the given mesh uv.
-fn coords_to_tile_index(uv: vec2<f32>, fdim: f32) -> u32 {
+/// Gets the tile index of the current animation frame of the given tile index.
+fn animate(i: u32, uv: vec2<f32>, fdim: f32) -> u32 {
+  if i >= arrayLength(&animations) {
+    return i;
+  }
+
+  var animation = animations[i];
+  if animation.y == 0u {
+    return i;
+  }
+
+  var duration = frames[animation.x + animation.y - 1u].y;
+  if duration == 0u {
+    return i;
+  }
+
+  // Offset each tile a little so neighboring tiles don't animate in lockstep.
+  var px = u32(floor(uv.x * fdim));
+  var py = u32(floor(uv.y * fdim));
+  var offset = (px * 7u + py * 13u) * 97u;
+  var time = (u32(globals.time * 1000.0) + offset) % duration;
+
+  for (var f = 0u; f < animation.y; f++) {
+    var frame = frames[animation.x + f];
+    if time < frame.y {
+      return frame.x;
+    }
+  }
+
+  return i;
+}
+
+/// Gets the offset within a layer of the tiles array for the given mesh uv, matching the row-major
+/// layout of `TileArray`.
+fn coords_to_tile_offset(uv: vec2<f32>, fdim: f32) -> u32 {
   var dim = u32(fdim);
   var px = u32(floor(uv.x * fdim));
   var py = u32(floor((1.0 - uv.y) * fdim));
 
-  return tiles[px * dim + py];
+  return py * dim + px;
 }
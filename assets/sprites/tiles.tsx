@@ -1,43 +1,509 @@
 <?xml version="1.0" encoding="UTF-8"?>
 <tileset version="1.10" tiledversion="1.10.2" name="tiles" tilewidth="8" tileheight="8" tilecount="400" columns="20">
+ <properties>
+  <property name="tile_attributes" type="bool" value="true"/>
+ </properties>
  <image source="tiles.png" width="160" height="160"/>
- <tile id="0" type="grass"/>
- <tile id="1" type="grass_1"/>
- <tile id="2" type="grass_2"/>
- <tile id="3" type="grass_3"/>
- <tile id="4" type="grass_water_south_west"/>
- <tile id="5" type="grass_water_south"/>
- <tile id="6" type="grass_water_south_east"/>
- <tile id="7" type="grass_water_west"/>
- <tile id="8" type="grass_water_east"/>
- <tile id="9" type="grass_water_north_west"/>
- <tile id="10" type="grass_water_north"/>
- <tile id="11" type="grass_water_north_east"/>
- <tile id="12" type="grass_water_north_and_east"/>
- <tile id="13" type="grass_water_north_and_west"/>
- <tile id="14" type="grass_water_south_and_east"/>
- <tile id="15" type="grass_water_south_and_west"/>
- <tile id="20" type="water_shallow"/>
- <tile id="21" type="water_shallow_wave"/>
- <tile id="22" type="water_shallow_wave_alt"/>
- <tile id="23" type="water_shallow_turbulent"/>
- <tile id="24" type="water_shallow_lilly"/>
- <tile id="25" type="water_shallow_dirt"/>
- <tile id="26" type="beach_top_left"/>
- <tile id="27" type="beach_top"/>
- <tile id="28" type="beach_top_right"/>
- <tile id="29" type="beach_bottom_left"/>
- <tile id="30" type="beach_bottom"/>
- <tile id="31" type="beach_bottom_right"/>
- <tile id="32" type="beach_left"/>
- <tile id="33" type="beach_right"/>
- <tile id="38" type="beach"/>
- <tile id="40" type="water_deep"/>
- <tile id="41" type="water_deep_wave"/>
- <tile id="42" type="water_deep_wave_alt"/>
- <tile id="43" type="water_deep_turbulent"/>
- <tile id="380" type="void"/>
- <tile id="381" type="void_grass"/>
- <tile id="382" type="void_brick"/>
- <tile id="383" type="void_water"/>
+ <tile id="0" type="grass">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="1"/>
+   <property name="flammability" type="float" value="0.6"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="1" type="grass_1">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="1"/>
+   <property name="flammability" type="float" value="0.6"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="2" type="grass_2">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="1"/>
+   <property name="flammability" type="float" value="0.6"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="3" type="grass_3">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="1"/>
+   <property name="flammability" type="float" value="0.6"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="4" type="grass_water_south_west">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="5" type="grass_water_south">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="6" type="grass_water_south_east">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="7" type="grass_water_west">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="8" type="grass_water_east">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="9" type="grass_water_north_west">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="10" type="grass_water_north">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="11" type="grass_water_north_east">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="12" type="grass_water_north_and_east">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="13" type="grass_water_north_and_west">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="14" type="grass_water_south_and_east">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="15" type="grass_water_south_and_west">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.4"/>
+   <property name="movement_cost" type="float" value="1.2"/>
+   <property name="terrain" value="grass"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="20" type="water_shallow">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+  <animation>
+   <frame tileid="20" duration="600"/>
+   <frame tileid="21" duration="300"/>
+   <frame tileid="22" duration="300"/>
+  </animation>
+ </tile>
+ <tile id="21" type="water_shallow_wave">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="22" type="water_shallow_wave_alt">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="23" type="water_shallow_turbulent">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="24" type="water_shallow_lilly">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="25" type="water_shallow_dirt">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="shallow"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="4"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="26" type="beach_top_left">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="27" type="beach_top">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="28" type="beach_top_right">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="29" type="beach_bottom_left">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="30" type="beach_bottom">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="31" type="beach_bottom_right">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="32" type="beach_left">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="33" type="beach_right">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="38" type="beach">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="0.2"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="sand"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="40" type="water_deep">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="deep"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="16"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+  <animation>
+   <frame tileid="40" duration="800"/>
+   <frame tileid="41" duration="400"/>
+   <frame tileid="42" duration="400"/>
+  </animation>
+ </tile>
+ <tile id="41" type="water_deep_wave">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="deep"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="16"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="42" type="water_deep_wave_alt">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="deep"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="16"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="43" type="water_deep_turbulent">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="depth" value="deep"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="16"/>
+   <property name="terrain" value="water"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="60" type="forest">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.6"/>
+   <property name="flammability" type="float" value="0.9"/>
+   <property name="movement_cost" type="float" value="2"/>
+   <property name="terrain" value="forest"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="61" type="plains">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="0.8"/>
+   <property name="flammability" type="float" value="0.7"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="plains"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="62" type="marsh">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0.4"/>
+   <property name="flammability" type="float" value="0.1"/>
+   <property name="movement_cost" type="float" value="3"/>
+   <property name="terrain" value="marsh"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="63" type="hills">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="0.4"/>
+   <property name="flammability" type="float" value="0.3"/>
+   <property name="movement_cost" type="float" value="2"/>
+   <property name="terrain" value="hills"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="64" type="mountain">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="8"/>
+   <property name="terrain" value="mountain"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="65" type="desert">
+  <properties>
+   <property name="buildable" type="bool" value="true"/>
+   <property name="fertility" type="float" value="0.05"/>
+   <property name="flammability" type="float" value="0.1"/>
+   <property name="movement_cost" type="float" value="1.5"/>
+   <property name="terrain" value="desert"/>
+   <property name="walkable" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="380" type="void">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="void"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="381" type="void_grass">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="void"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="382" type="void_brick">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="void"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <tile id="383" type="void_water">
+  <properties>
+   <property name="buildable" type="bool" value="false"/>
+   <property name="fertility" type="float" value="0"/>
+   <property name="flammability" type="float" value="0"/>
+   <property name="movement_cost" type="float" value="1"/>
+   <property name="terrain" value="void"/>
+   <property name="walkable" type="bool" value="false"/>
+  </properties>
+ </tile>
+ <wangsets>
+  <wangset name="beach" type="edge" tile="38">
+   <wangcolor name="sand" color="#ffe08a" tile="38" probability="1"/>
+   <wangcolor name="water" color="#3c78d8" tile="20" probability="1"/>
+   <wangtile tileid="26" wangid="2,0,1,0,1,0,2,0"/>
+   <wangtile tileid="27" wangid="2,0,1,0,1,0,1,0"/>
+   <wangtile tileid="28" wangid="2,0,2,0,1,0,1,0"/>
+   <wangtile tileid="29" wangid="1,0,1,0,2,0,2,0"/>
+   <wangtile tileid="30" wangid="1,0,1,0,2,0,1,0"/>
+   <wangtile tileid="31" wangid="1,0,2,0,2,0,1,0"/>
+   <wangtile tileid="32" wangid="1,0,1,0,1,0,2,0"/>
+   <wangtile tileid="33" wangid="1,0,2,0,1,0,1,0"/>
+   <wangtile tileid="38" wangid="1,0,1,0,1,0,1,0"/>
+  </wangset>
+  <wangset name="grass" type="mixed" tile="0">
+   <wangcolor name="grass" color="#6aa84f" tile="0" probability="1"/>
+   <wangcolor name="water" color="#3c78d8" tile="20" probability="1"/>
+   <wangtile tileid="0" wangid="1,1,1,1,1,1,1,1"/>
+   <wangtile tileid="4" wangid="1,1,1,0,2,0,2,0"/>
+   <wangtile tileid="5" wangid="1,1,1,0,2,0,1,1"/>
+   <wangtile tileid="6" wangid="1,0,2,0,2,0,1,1"/>
+   <wangtile tileid="7" wangid="1,1,1,1,1,0,2,0"/>
+   <wangtile tileid="8" wangid="1,0,2,0,1,1,1,1"/>
+   <wangtile tileid="9" wangid="2,0,1,1,1,0,2,0"/>
+   <wangtile tileid="10" wangid="2,0,1,1,1,1,1,0"/>
+   <wangtile tileid="11" wangid="2,0,2,0,1,1,1,0"/>
+   <wangtile tileid="12" wangid="1,2,1,1,1,1,1,1"/>
+   <wangtile tileid="13" wangid="1,1,1,1,1,1,1,2"/>
+   <wangtile tileid="14" wangid="1,1,1,2,1,1,1,1"/>
+   <wangtile tileid="15" wangid="1,1,1,1,1,2,1,1"/>
+  </wangset>
+ </wangsets>
 </tileset>
@@ -1,14 +1,38 @@
 mod tiled;
 
+use heck::ToUpperCamelCase;
 use std::error::Error;
 use std::path::PathBuf;
 use std::{env, fs};
 
 fn main() -> Result<(), Box<dyn Error>> {
   let out = PathBuf::from(env::var("OUT_DIR")?);
-  let overworld = tiled::get_tile_set_enum("assets/sprites/tiles.tsx", "Tiles")?;
 
-  fs::write(out.join("tiles.rs"), overworld)?;
+  let mut tile_sets = Vec::new();
+
+  for entry in fs::read_dir("assets/sprites")? {
+    let path = entry?.path();
+    if path.extension().is_some_and(|ext| ext == "tsx") {
+      tile_sets.push(path);
+    }
+  }
+
+  tile_sets.sort();
+
+  let mut names = Vec::with_capacity(tile_sets.len());
+  let mut sprites = String::new();
+
+  for path in &tile_sets {
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let name = name.to_upper_camel_case();
+
+    sprites.push_str(&tiled::get_tile_set_enum(path, &name)?);
+    names.push(name);
+  }
+
+  sprites.push_str(&tiled::get_sprite_enum(&names)?);
+
+  fs::write(out.join("sprites.rs"), sprites)?;
 
   Ok(())
 }
@@ -1,7 +1,25 @@
-use heck::ToUpperCamelCase;
+use anyhow::{bail, Context};
+use heck::{ToSnakeCase, ToUpperCamelCase};
 use quote::{format_ident, quote};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
-use tiled::Loader;
+use tiled::{Loader, PropertyValue, Tileset};
+
+/// The gameplay attributes every tile must define with the type of their Tiled property.
+///
+/// These line up with the fields of `TileAttributes` in `src/sprites.rs`.
+const ATTRIBUTES: &[(&str, AttributeKind)] = &[
+  ("movement_cost", AttributeKind::Float),
+  ("buildable", AttributeKind::Bool),
+  ("fertility", AttributeKind::Float),
+  ("flammability", AttributeKind::Float),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum AttributeKind {
+  Bool,
+  Float,
+}
 
 pub fn get_tile_set_enum<P: AsRef<Path>>(path: P, name: &str) -> anyhow::Result<String> {
   let mut loader = Loader::new();
@@ -9,20 +27,41 @@ pub fn get_tile_set_enum<P: AsRef<Path>>(path: P, name: &str) -> anyhow::Result<
   let name = format_ident!("{name}");
 
   let tileset = loader.load_tsx_tileset(&path)?;
-  let tiles = tileset
+  let mut tiles = tileset
     .tiles()
     .filter_map(|(i, tile)| tile.user_type.as_ref().map(|val| (i, val.clone())))
     .collect::<Vec<_>>();
 
+  tiles.sort_by_key(|(i, _)| *i);
+
+  let predicates = get_predicates(&tileset, &name, &tiles)?;
+  let attributes = match tileset.properties.get("tile_attributes") {
+    Some(PropertyValue::BoolValue(true)) => {
+      let attributes = get_attributes(&tileset, &tiles)?;
+      let len = attributes.len();
+
+      Some(quote! {
+        /// Gets the gameplay attributes of each tile indexed by its atlas index.
+        pub const ATTRIBUTES: [TileAttributes; #len] = [
+          #(#attributes),*
+        ];
+      })
+    }
+    _ => None,
+  };
+  let wang_sets = get_wang_sets(&tileset, &name, &tiles)?;
+  let ids = get_ids(&tiles)?;
+  let atlas = get_atlas(&tileset)?;
+  let animations = get_animations(&tileset, &name, &tiles)?;
+
   let path = tileset.image.unwrap();
   let path = path.source.to_string_lossy().replace("assets/", "");
 
-  let variants = tiles.iter().map(|(i, name)| {
-    let i = *i as isize;
+  let variants = tiles.iter().enumerate().map(|(n, (i, name))| {
     let ident = name.to_upper_camel_case();
     let ident = format_ident!("{ident}");
     let docs = format!(" Tile type `{name}` at index `{i}`");
-    let is_default = if i == 0 {
+    let is_default = if n == 0 {
       Some(quote! { #[default] })
     } else {
       None
@@ -31,19 +70,20 @@ pub fn get_tile_set_enum<P: AsRef<Path>>(path: P, name: &str) -> anyhow::Result<
     quote! {
       #[doc = #docs]
       #is_default
-      #ident = #i
+      #ident
     }
   });
 
   let count = tiles.len();
-  let names = tiles.iter().map(|(_, name)| {
-    let name = name.to_upper_camel_case();
-
-    format_ident!("{name}")
-  });
+  let names = tiles
+    .iter()
+    .map(|(_, name)| format_ident!("{}", name.to_upper_camel_case()))
+    .collect::<Vec<_>>();
+  let indices = tiles.iter().map(|(i, _)| i);
+  let tile_names = tiles.iter().map(|(_, name)| name).collect::<Vec<_>>();
 
   Ok(prettyplease::unparse(&syn::parse2(quote! {
-    #[derive(Default, Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+    #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
     pub enum #name {
       #(#variants),*
     }
@@ -58,6 +98,384 @@ pub fn get_tile_set_enum<P: AsRef<Path>>(path: P, name: &str) -> anyhow::Result<
       pub fn path() -> &'static str {
         #path
       }
+
+      /// Gets the layout of the tile set image.
+      pub const ATLAS: TileAtlas = #atlas;
+
+      /// Gets the index of the tile in the tile set image.
+      ///
+      /// This changes whenever the tile set is rearranged so it must not be persisted, use
+      /// [Self::id] or [Self::name] instead.
+      pub const fn index(&self) -> u32 {
+        match self {
+          #(Self::#names => #indices),*
+        }
+      }
+
+      /// Gets the tile type name.
+      pub const fn name(&self) -> &'static str {
+        match self {
+          #(Self::#names => #tile_names),*
+        }
+      }
+
+      /// Gets the tile with the given tile type name.
+      pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+          #(#tile_names => Some(Self::#names),)*
+          _ => None,
+        }
+      }
+
+      /// Gets the stable id of the tile, a hash of its tile type name.
+      pub const fn id(&self) -> u32 {
+        match self {
+          #(Self::#names => #ids),*
+        }
+      }
+
+      /// Gets the tile with the given stable id.
+      pub const fn from_id(id: u32) -> Option<Self> {
+        match id {
+          #(#ids => Some(Self::#names),)*
+          _ => None,
+        }
+      }
+
+      #(#predicates)*
+
+      #attributes
+
+      /// Gets the frames of animated tiles as `(tile index, duration in ms)`.
+      pub const ANIMATIONS: &'static [(#name, &'static [(u32, u32)])] = &[
+        #(#animations),*
+      ];
+
+      /// Gets the autotiling rules from the tile set wang sets.
+      pub const WANG_SETS: &'static [WangSet<#name>] = &[
+        #(#wang_sets),*
+      ];
+    }
+  })?))
+}
+
+/// Gets the `Sprite` enum with a variant for each generated tile set enum.
+pub fn get_sprite_enum(names: &[String]) -> anyhow::Result<String> {
+  let names = names
+    .iter()
+    .map(|name| format_ident!("{name}"))
+    .collect::<Vec<_>>();
+
+  Ok(prettyplease::unparse(&syn::parse2(quote! {
+    /// A sprite from any of the tile sets.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Sprite {
+      #(#names(#names)),*
+    }
+
+    impl Sprite {
+      /// Gets the path to the spritesheet texture.
+      pub fn path(&self) -> &'static str {
+        match self {
+          #(Self::#names(_) => #names::path()),*
+        }
+      }
     }
+
+    #(
+      impl From<#names> for Sprite {
+        fn from(value: #names) -> Self {
+          Self::#names(value)
+        }
+      }
+    )*
   })?))
 }
+
+/// Gets a category predicate for each custom tile property.
+///
+/// A string property such as `terrain = "water"` emits `is_water` for every value used in the
+/// tile set and a bool property such as `walkable = true` emits `is_walkable`.
+fn get_predicates(
+  tileset: &Tileset,
+  name: &syn::Ident,
+  tiles: &[(u32, String)],
+) -> anyhow::Result<Vec<syn::ImplItemFn>> {
+  let mut predicates = BTreeMap::<String, (String, String, Vec<syn::Ident>)>::new();
+
+  for (i, tile_name) in tiles {
+    let tile = tileset.get_tile(*i).unwrap();
+    let ident = format_ident!("{}", tile_name.to_upper_camel_case());
+
+    for (key, value) in &tile.properties {
+      if ATTRIBUTES.iter().any(|(attribute, _)| attribute == key) {
+        continue;
+      }
+
+      let (predicate, docs, matches) = match value {
+        PropertyValue::StringValue(value) => (
+          value.to_snake_case(),
+          format!(" Whether the tile has `{key}` set to `{value}`."),
+          true,
+        ),
+        PropertyValue::BoolValue(value) => (
+          key.to_snake_case(),
+          format!(" Whether the tile is `{key}`."),
+          *value,
+        ),
+        _ => continue,
+      };
+
+      let (existing, _, variants) = predicates
+        .entry(predicate.clone())
+        .or_insert_with(|| (key.clone(), docs, Vec::new()));
+
+      if existing != key {
+        bail!("tile `{tile_name}` property `{key}` emits `is_{predicate}` which is already used");
+      }
+
+      if matches {
+        variants.push(ident.clone());
+      }
+    }
+  }
+
+  Ok(
+    predicates
+      .into_iter()
+      .map(|(predicate, (_, docs, variants))| {
+        let predicate = format_ident!("is_{predicate}");
+        let body = if variants.is_empty() {
+          quote! { false }
+        } else {
+          quote! { matches!(self, #(#name::#variants)|*) }
+        };
+
+        syn::parse_quote! {
+          #[doc = #docs]
+          pub fn #predicate(&self) -> bool {
+            #body
+          }
+        }
+      })
+      .collect(),
+  )
+}
+
+/// Gets a `TileAttributes` expression for each tile index up to the last typed tile.
+///
+/// Only generated for tile sets with the `tile_attributes` property. Indices without a typed tile
+/// aren't part of the enum and get `TileAttributes::NONE`.
+fn get_attributes(tileset: &Tileset, tiles: &[(u32, String)]) -> anyhow::Result<Vec<syn::Expr>> {
+  let len = tiles.last().map_or(0, |(i, _)| *i as usize + 1);
+  let mut attributes = vec![syn::parse_quote! { TileAttributes::NONE }; len];
+
+  for (i, tile_name) in tiles {
+    let tile = tileset.get_tile(*i).unwrap();
+    let fields = ATTRIBUTES
+      .iter()
+      .map(|(key, kind)| {
+        let field = format_ident!("{key}");
+        let value = match (kind, tile.properties.get(*key)) {
+          (AttributeKind::Bool, Some(PropertyValue::BoolValue(value))) => quote! { #value },
+          (AttributeKind::Float, Some(PropertyValue::FloatValue(value))) => quote! { #value },
+          (kind, Some(value)) => {
+            bail!("tile `{tile_name}` property `{key}` should be a {kind:?} but was {value:?}")
+          }
+          (_, None) => bail!("tile `{tile_name}` is missing the required property `{key}`"),
+        };
+
+        Ok(quote! { #field: #value })
+      })
+      .collect::<anyhow::Result<Vec<_>>>()?;
+
+    attributes[*i as usize] = syn::parse_quote! {
+      TileAttributes {
+        #(#fields),*
+      }
+    };
+  }
+
+  Ok(attributes)
+}
+
+/// Gets a `TileAtlas` expression for the layout of the tile set image.
+///
+/// The chunk shader only renders square tiles so this fails when the tiles aren't square or the
+/// image can't hold every tile with the given margin and spacing.
+fn get_atlas(tileset: &Tileset) -> anyhow::Result<syn::Expr> {
+  let Tileset {
+    tile_width,
+    tile_height,
+    margin,
+    spacing,
+    columns,
+    tilecount,
+    ..
+  } = *tileset;
+
+  if tile_width == 0 || tile_width != tile_height {
+    bail!(
+      "tile set `{}` must use square tiles but has {tile_width}x{tile_height} tiles",
+      tileset.name
+    );
+  }
+
+  if columns == 0 {
+    bail!("tile set `{}` has no columns", tileset.name);
+  }
+
+  let image = tileset
+    .image
+    .as_ref()
+    .with_context(|| format!("tile set `{}` has no image", tileset.name))?;
+
+  let rows = tilecount.div_ceil(columns);
+  let width = 2 * margin + columns * tile_width + (columns - 1) * spacing;
+  let height = 2 * margin + rows * tile_height + rows.saturating_sub(1) * spacing;
+  if width > image.width as u32 || height > image.height as u32 {
+    bail!(
+      "tile set `{}` needs a {width}x{height} image for {columns} columns and {rows} rows but `{}` is {}x{}",
+      tileset.name,
+      image.source.display(),
+      image.width,
+      image.height,
+    );
+  }
+
+  Ok(syn::parse_quote! {
+    TileAtlas {
+      tile_size: #tile_width,
+      margin: #margin,
+      spacing: #spacing,
+      columns: #columns,
+    }
+  })
+}
+
+/// Gets the stable id of each tile from the FNV-1a hash of its tile type name.
+fn get_ids(tiles: &[(u32, String)]) -> anyhow::Result<Vec<u32>> {
+  let mut seen = HashMap::<u32, &str>::new();
+
+  tiles
+    .iter()
+    .map(|(_, name)| {
+      let id = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+      });
+
+      if let Some(existing) = seen.insert(id, name) {
+        bail!("tile types `{existing}` and `{name}` have the same id `{id}`");
+      }
+
+      Ok(id)
+    })
+    .collect()
+}
+
+/// Gets the animation frames of each animated tile.
+fn get_animations(
+  tileset: &Tileset,
+  name: &syn::Ident,
+  tiles: &[(u32, String)],
+) -> anyhow::Result<Vec<syn::Expr>> {
+  tiles
+    .iter()
+    .filter_map(|(i, tile_name)| {
+      let tile = tileset.get_tile(*i).unwrap();
+      let frames = tile.animation.as_ref()?;
+
+      Some((tile_name, frames.clone()))
+    })
+    .map(|(tile_name, frames)| {
+      let ident = format_ident!("{}", tile_name.to_upper_camel_case());
+
+      if frames.is_empty() || frames.iter().all(|frame| frame.duration == 0) {
+        bail!("tile `{tile_name}` has an animation without any duration");
+      }
+
+      if let Some(frame) = frames
+        .iter()
+        .find(|frame| frame.tile_id >= tileset.tilecount)
+      {
+        bail!(
+          "tile `{tile_name}` animates to tile `{}` outside of the tile set",
+          frame.tile_id
+        );
+      }
+
+      let frames = frames.iter().map(|frame| {
+        let tile_id = frame.tile_id;
+        let duration = frame.duration;
+
+        quote! { (#tile_id, #duration) }
+      });
+
+      Ok(syn::parse_quote! { (#name::#ident, &[#(#frames),*]) })
+    })
+    .collect()
+}
+
+/// Gets a `WangSet` expression for each wang set in the tile set.
+///
+/// Wang colors are matched against tiles with the `is_{color}` predicate of the generated enum,
+/// so a color named `water` uses `is_water`.
+fn get_wang_sets(
+  tileset: &Tileset,
+  name: &syn::Ident,
+  tiles: &[(u32, String)],
+) -> anyhow::Result<Vec<syn::Expr>> {
+  let idents = tiles
+    .iter()
+    .map(|(i, tile)| (*i, format_ident!("{}", tile.to_upper_camel_case())))
+    .collect::<HashMap<_, _>>();
+
+  tileset
+    .wang_sets
+    .iter()
+    .map(|set| {
+      let set_name = &set.name;
+
+      if set.wang_colors.len() < 2 {
+        bail!("wang set `{set_name}` needs a terrain color and at least one neighbor color");
+      }
+
+      let colors = set.wang_colors.iter().map(|color| {
+        let predicate = format_ident!("is_{}", color.name.to_snake_case());
+
+        quote! { #name::#predicate }
+      });
+
+      let mut wang_tiles = set.wang_tiles.iter().collect::<Vec<_>>();
+
+      wang_tiles.sort_by_key(|(i, _)| **i);
+
+      let wang_tiles = wang_tiles
+        .into_iter()
+        .map(|(i, tile)| {
+          let ident = idents
+            .get(i)
+            .with_context(|| format!("wang set `{set_name}` uses tile `{i}` which has no type"))?;
+
+          let wang_id = tile.wang_id.0;
+          if let Some(color) = wang_id
+            .iter()
+            .find(|c| **c as usize > set.wang_colors.len())
+          {
+            bail!("wang set `{set_name}` uses unknown color `{color}` on tile `{i}`");
+          }
+
+          Ok(quote! { (#name::#ident, [#(#wang_id),*]) })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+      Ok(syn::parse_quote! {
+        WangSet {
+          name: #set_name,
+          colors: &[#(#colors),*],
+          tiles: &[#(#wang_tiles),*],
+        }
+      })
+    })
+    .collect()
+}
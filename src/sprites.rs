@@ -1,57 +1,165 @@
 use rand::{thread_rng, Rng};
 
-include!(concat!(env!("OUT_DIR"), "/tiles.rs"));
+include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
 
-pub enum Sprite {
-  Tiles(Tiles),
+/// Gameplay attributes of a tile generated from the tile set properties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileAttributes {
+  /// The relative cost of moving across the tile.
+  pub movement_cost: f32,
+  /// Whether structures can be built on the tile.
+  pub buildable: bool,
+  /// How well crops grow on the tile defined as a value [0-1].
+  pub fertility: f32,
+  /// How easily fire spreads across the tile defined as a value [0-1].
+  pub flammability: f32,
 }
 
-impl Sprite {
-  /// Gets the path to the spritesheet texture.
-  pub fn path(&self) -> &'static str {
-    match self {
-      Self::Tiles(_) => Tiles::path(),
+impl TileAttributes {
+  /// The attributes of an index without a tile.
+  pub const NONE: Self = Self {
+    movement_cost: f32::INFINITY,
+    buildable: false,
+    fertility: 0.0,
+    flammability: 0.0,
+  };
+}
+
+pub use self::atlas::TileAtlas;
+
+// `ShaderType` checks each field with a function that is never called, next to the struct rather
+// than within it, so the lint can only be allowed for the enclosing module.
+#[allow(dead_code)]
+mod atlas {
+  use bevy::render::render_resource::ShaderType;
+
+  /// The layout of a tile set image generated from the tile set.
+  ///
+  /// Tiles are laid out row by row with `columns` tiles per row, `spacing` pixels between tiles
+  /// and `margin` pixels around the image.
+  #[derive(ShaderType, Debug, Clone, Copy, PartialEq, Eq)]
+  pub struct TileAtlas {
+    /// The width and height of a tile in pixels.
+    pub tile_size: u32,
+    pub margin: u32,
+    pub spacing: u32,
+    pub columns: u32,
+  }
+}
+
+/// Autotiling rules generated from a Tiled wang set.
+///
+/// Wang ids list a color index for each neighbor clockwise starting at the top edge, where `0`
+/// means any color and `1` is the terrain the set applies to.
+pub struct WangSet<T: 'static> {
+  pub name: &'static str,
+  /// Predicates for the wang colors, the first being the terrain the set applies to.
+  pub colors: &'static [fn(&T) -> bool],
+  /// Tiles with their wang ids.
+  pub tiles: &'static [(T, [u8; 8])],
+}
+
+impl<T: Copy + PartialEq> WangSet<T> {
+  /// Whether the wang set applies to the given tile.
+  pub fn contains(&self, tile: &T) -> bool {
+    self.colors.first().is_some_and(|is| is(tile))
+  }
+
+  /// Gets the wang color index of the given neighbor, defaulting to the terrain color.
+  pub fn color(&self, tile: Option<&T>) -> u8 {
+    tile
+      .and_then(|tile| self.colors.iter().skip(1).position(|is| is(tile)))
+      .map_or(1, |i| i as u8 + 2)
+  }
+
+  /// Picks the tile matching the given neighbors ordered clockwise starting at the top edge.
+  ///
+  /// Tiles matching every neighbor are preferred, otherwise the tile matching the most
+  /// transitions is picked with ties going to the first tile in the set. Tiles outside of the
+  /// set are kept when there's no transition, so variations of the terrain aren't replaced.
+  pub fn autotile(&self, tile: T, neighbors: [Option<T>; 8]) -> T {
+    let wang_id = neighbors.map(|neighbor| self.color(neighbor.as_ref()));
+    let mut best = None;
+
+    for (candidate, candidate_id) in self.tiles {
+      let mut exact = true;
+      let mut score = 0;
+
+      for (want, have) in candidate_id.iter().zip(wang_id) {
+        match *want {
+          0 => {}
+          want if want == have && want != 1 => score += 1,
+          want if want == have => {}
+          1 => exact = false,
+          _ => {
+            score = -1;
+            break;
+          }
+        }
+      }
+
+      if score < 0 {
+        continue;
+      }
+
+      let is_better = match best {
+        Some((_, best)) => (exact, score) > best,
+        None => true,
+      };
+
+      if is_better {
+        best = Some((*candidate, (exact, score)));
+      }
+    }
+
+    match best {
+      Some((_, (_, 0))) if !self.is_transition(&tile) => tile,
+      Some((candidate, _)) => candidate,
+      None => tile,
     }
   }
+
+  /// Whether the given tile is one of the transitions of the set.
+  pub fn is_transition(&self, tile: &T) -> bool {
+    self
+      .tiles
+      .iter()
+      .any(|(candidate, wang_id)| candidate == tile && wang_id.iter().any(|c| *c > 1))
+  }
 }
 
 impl Tiles {
-  pub fn is_grass(&self) -> bool {
-    matches!(
-      self,
-      Self::Grass | Self::Grass1 | Self::Grass2 | Self::Grass3
-    )
-  }
-
-  pub fn is_sand(&self) -> bool {
-    matches!(
-      self,
-      Self::Beach
-        | Self::BeachTop
-        | Self::BeachTopLeft
-        | Self::BeachTopRight
-        | Self::BeachRight
-        | Self::BeachLeft
-        | Self::BeachBottom
-        | Self::BeachBottomLeft
-        | Self::BeachBottomRight
-    )
+  /// Gets the gameplay attributes of the tile.
+  pub fn attributes(&self) -> &'static TileAttributes {
+    &Self::ATTRIBUTES[self.index() as usize]
   }
 
-  pub fn is_land(&self) -> bool {
-    !self.is_water()
+  pub fn movement_cost(&self) -> f32 {
+    self.attributes().movement_cost
+  }
+
+  pub fn is_buildable(&self) -> bool {
+    self.attributes().buildable
   }
 
-  pub fn is_water(&self) -> bool {
-    self.is_water_deep() || self.is_water_shallow()
+  pub fn fertility(&self) -> f32 {
+    self.attributes().fertility
+  }
+
+  pub fn flammability(&self) -> f32 {
+    self.attributes().flammability
+  }
+
+  pub fn is_land(&self) -> bool {
+    !self.is_water()
   }
 
   pub fn is_water_deep(&self) -> bool {
-    matches!(self, Self::WaterDeep)
+    self.is_water() && self.is_deep()
   }
 
   pub fn is_water_shallow(&self) -> bool {
-    matches!(self, Self::WaterShallow)
+    self.is_water() && self.is_shallow()
   }
 
   pub fn grass<R: Rng>(mut rng: R) -> Tiles {
@@ -82,3 +190,33 @@ impl Tiles {
   //   .unwrap()
   // }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  #[test]
+  fn ids_are_stable() {
+    // Saved chunks store these, so they must never change.
+    assert_eq!(Tiles::Grass.id(), 2993663101);
+    assert_eq!(Tiles::Beach.id(), 4105357384);
+    assert_eq!(Tiles::WaterDeep.id(), 1259109255);
+    assert_eq!(Tiles::Void.id(), 1219850847);
+
+    let ids = Tiles::ALL.map(|tile| tile.id());
+    assert_eq!(ids.into_iter().collect::<HashSet<_>>().len(), ids.len());
+  }
+
+  #[test]
+  fn ids_and_names_round_trip() {
+    for tile in Tiles::ALL {
+      assert_eq!(Tiles::from_id(tile.id()), Some(tile));
+      assert_eq!(Tiles::from_name(tile.name()), Some(tile));
+    }
+
+    assert_eq!(Tiles::from_name("water_deep"), Some(Tiles::WaterDeep));
+    assert_eq!(Tiles::from_name("WaterDeep"), None);
+    assert_eq!(Tiles::from_id(0), None);
+  }
+}
@@ -6,115 +6,400 @@ mod render;
 mod neighbors;
 mod edit;
 mod debug;
+mod save;
+mod codec;
+mod region;
+mod coords;
+mod map;
+mod data;
 
-use self::chunk::{ChunkId, ChunkTiles, Chunks};
+use self::chunk::{ChunkDirty, ChunkGenerating, ChunkId, ChunkTiles, Chunks, TileLayer};
+use self::data::{TileDataAppExt, TileDataRegistry};
 use self::edit::EditorConf;
-use self::generate::GenerateConf;
-use self::render::ChunkMaterial;
+use self::generate::{Fertility, GenerateConf};
+use self::render::{ChunkMaterial, TileMapAssets};
+use self::save::ChunkStore;
 use crate::camera::GameCamera;
-use bevy::input::common_conditions::input_pressed;
+use crate::sprites::Tiles;
+use bevy::ecs::world::EntityRef;
+use bevy::input::common_conditions::{input_just_pressed, input_pressed};
 use bevy::prelude::*;
 use bevy::sprite::{Material2dPlugin, MaterialMesh2dBundle};
 use bevy::utils::HashSet;
 
-/// The sqrt of the number of tiles within a chunk.
-pub const CHUNK_TILES_SQ: usize = 50;
-/// The total number of tiles within a chunk.
-pub const CHUNK_TILES: usize = CHUNK_TILES_SQ * CHUNK_TILES_SQ;
-/// The sqrt of the size of a chunk in pixels.
-pub const CHUNK_SIZE_SQ: f32 = CHUNK_TILES_SQ as f32 * 8.0;
-
 pub struct TileMapPlugin;
 
 impl Plugin for TileMapPlugin {
   fn build(&self, app: &mut App) {
+    app.world.get_resource_or_insert_with(TileMapConf::default);
+
     app
       .insert_resource(Chunks::default())
       .insert_resource(EditorConf::default())
-      .insert_resource(GenerateConf {
-        seed: 0xdead,
-        continent_scale: 1.0
-      })
+      .insert_resource(ChunkStore::default())
+      .insert_resource(ChunkLoadConf::default())
+      .insert_resource(GenerateConf::default())
+      .init_resource::<TileDataRegistry>()
+      .add_tile_data::<Fertility>()
       .add_plugins(Material2dPlugin::<render::ChunkMaterial>::default())
+      .add_systems(Startup, render::setup)
       .add_systems(Update, (
         chunk::spawn, 
-        chunk::cleanup,
+        chunk::poll_generating.after(chunk::spawn),
+        // Generated chunks must have lost ChunkGenerating by the time cleanup autotiles them.
+        apply_deferred.after(chunk::poll_generating).before(chunk::cleanup),
+        chunk::cleanup.after(chunk::poll_generating).after(edit::draw),
         update_precense, 
-        render::update_material,
+        render::update_material.after(chunk::cleanup),
         edit::draw.run_if(input_pressed(MouseButton::Left)),
         debug::chunk_wireframes,
         edit::menu,
+        save::save.run_if(input_just_pressed(KeyCode::F5)),
+        save::load.before(chunk::cleanup).run_if(input_just_pressed(KeyCode::F9)),
         // ..
       ))
     // ..
     ;
   }
+
+  fn finish(&self, app: &mut App) {
+    // Checked once every plugin is built so confs inserted after this plugin are checked too.
+    if let Err(err) = app.world.resource::<TileMapConf>().validate() {
+      panic!("invalid tile map dimensions: {err}");
+    }
+  }
+}
+
+/// Tile map dimensions.
+///
+/// Insert one into the app to use other dimensions than the defaults, it's validated by
+/// [TileMapPlugin] before the app runs.
+///
+/// These are read-only once the app has started. The shared chunk mesh, the tiles and data
+/// layers of every loaded and stored chunk, and the chunk positions are all sized from them, so
+/// changing them would mix chunks of different sizes. Worlds are saved along with their chunk
+/// dimension and only load with the same one.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct TileMapConf {
+  /// The sqrt of the number of tiles within a chunk.
+  pub chunk_tiles_sq: u32,
+  /// The size of a tile in world units.
+  pub tile_size: f32,
+}
+
+impl TileMapConf {
+  /// Gets the total number of tiles within a chunk.
+  pub fn chunk_tiles(&self) -> usize {
+    self.chunk_tiles_sq as usize * self.chunk_tiles_sq as usize
+  }
+
+  /// Gets the sqrt of the size of a chunk in world units.
+  pub fn chunk_size_sq(&self) -> f32 {
+    self.chunk_tiles_sq as f32 * self.tile_size
+  }
+
+  /// Checks that the chunk shader can render chunks of these dimensions.
+  ///
+  /// The shader indexes the tiles of every layer of a chunk with a `u32`.
+  pub fn validate(&self) -> Result<(), String> {
+    if self.chunk_tiles_sq == 0 {
+      return Err("chunks must hold at least one tile".into());
+    }
+
+    if self.chunk_tiles() * TileLayer::COUNT > u32::MAX as usize {
+      return Err(format!(
+        "chunks of {0}x{0} tiles are too large for the chunk shader",
+        self.chunk_tiles_sq
+      ));
+    }
+
+    if !(self.tile_size.is_finite() && self.tile_size > 0.0) {
+      return Err(format!("tiles must have a positive size, not {}", self.tile_size));
+    }
+
+    Ok(())
+  }
+}
+
+impl Default for TileMapConf {
+  fn default() -> Self {
+    Self {
+      chunk_tiles_sq: 50,
+      tile_size: Tiles::ATLAS.tile_size as f32,
+    }
+  }
+}
+
+/// Chunk loading configuration.
+#[derive(Resource)]
+pub struct ChunkLoadConf {
+  /// The number of chunks around the view that are loaded.
+  pub load_radius: i32,
+  /// The number of chunks around the view past which chunks are unloaded.
+  ///
+  /// Keeping this larger than `load_radius` stops chunks near the edge from being unloaded and
+  /// loaded again as the camera moves back and forth.
+  pub unload_radius: i32,
+  /// The maximum number of chunks spawned per frame.
+  pub spawn_budget: usize,
 }
 
-#[allow(clippy::type_complexity)]
+impl Default for ChunkLoadConf {
+  fn default() -> Self {
+    Self {
+      load_radius: 4,
+      unload_radius: 6,
+      spawn_budget: 8,
+    }
+  }
+}
+
+/// The chunks to spawn and despawn for a view.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PresencePlan {
+  /// Chunks to spawn nearest to the center of the view first.
+  spawn: Vec<ChunkId>,
+  despawn: Vec<ChunkId>,
+}
+
+/// Plans which chunks to spawn and despawn for the given view in world coordinates.
+fn plan_presence<I>(conf: &ChunkLoadConf, map: &TileMapConf, view: Rect, loaded: I) -> PresencePlan
+where
+  I: IntoIterator<Item = ChunkId>,
+{
+  let min = ChunkId::from_world(view.min, map);
+  let max = ChunkId::from_world(view.max, map);
+  let center = view.center();
+
+  let within = |id: ChunkId, radius: i32| {
+    id.cmpge(min.0 - radius).all() && id.cmple(max.0 + radius).all()
+  };
+
+  let loaded = loaded.into_iter().collect::<HashSet<_>>();
+  let unload_radius = conf.unload_radius.max(conf.load_radius);
+
+  let mut despawn = loaded
+    .iter()
+    .copied()
+    .filter(|id| !within(*id, unload_radius))
+    .collect::<Vec<_>>();
+
+  despawn.sort_by_key(|id| (id.x, id.y));
+
+  let mut spawn = Vec::new();
+  for x in min.x - conf.load_radius..=max.x + conf.load_radius {
+    for y in min.y - conf.load_radius..=max.y + conf.load_radius {
+      let id = ChunkId(IVec2::new(x, y));
+      if !loaded.contains(&id) {
+        spawn.push(id);
+      }
+    }
+  }
+
+  spawn.sort_by(|a, b| {
+    let a_dist = a.to_world_rect(map).center().distance_squared(center);
+    let b_dist = b.to_world_rect(map).center().distance_squared(center);
+
+    a_dist.total_cmp(&b_dist).then((a.x, a.y).cmp(&(b.x, b.y)))
+  });
+
+  spawn.truncate(conf.spawn_budget);
+
+  PresencePlan { spawn, despawn }
+}
+
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all)]
 fn update_precense(
+  conf: Res<ChunkLoadConf>,
+  map: Res<TileMapConf>,
+  registry: Res<TileDataRegistry>,
   mut chunks: ResMut<Chunks>,
+  mut store: ResMut<ChunkStore>,
   mut commands: Commands,
-  mut meshes: ResMut<Assets<Mesh>>,
   mut materials: ResMut<Assets<ChunkMaterial>>,
-  assets: Res<AssetServer>,
-  cameras: Query<
-    (&Transform, &OrthographicProjection),
-    (
-      With<GameCamera>,
-      Or<(Changed<Transform>, Changed<OrthographicProjection>)>,
-    ),
-  >,
+  assets: Res<TileMapAssets>,
+  dirty: Query<EntityRef, (With<ChunkDirty>, Without<ChunkGenerating>)>,
+  cameras: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
 ) {
-  let plane = shape::Quad::new(Vec2::splat(CHUNK_SIZE_SQ));
-  let mesh = meshes.add(Mesh::from(plane));
-
   for (transform, projection) in &cameras {
-    let mut in_view = HashSet::new();
+    let view = Rect {
+      min: projection.area.min + transform.translation.xy(),
+      max: projection.area.max + transform.translation.xy(),
+    };
+
+    let plan = plan_presence(&conf, &map, view, chunks.keys().copied());
+
+    for id in plan.despawn {
+      let Some(entity) = chunks.remove(&id) else {
+        continue;
+      };
+
+      if let Ok(entity) = dirty.get(entity) {
+        store.insert_entity(&registry, &entity);
+      }
+
+      commands.entity(entity).despawn_recursive();
+    }
 
-    let view_min = projection.area.min + transform.translation.xy();
-    let view_max = projection.area.max + transform.translation.xy();
+    for id in plan.spawn {
+      _ = tracing::debug_span!("spawn", id = ?id).entered();
 
-    let min = ChunkId::from_world(view_min);
-    let max = ChunkId::from_world(view_max);
+      let entity = commands.spawn((
+        id,
+        ChunkTiles::new(&map),
+        ChunkGenerating::default(),
+        MaterialMesh2dBundle {
+          mesh: assets.mesh.clone().into(),
+          material: materials.add(ChunkMaterial::new(&assets, &map)),
+          transform: Transform::from_translation(id.to_world_rect(&map).center().extend(0.0)),
+          // Shown once the tiles are restored or generated.
+          visibility: Visibility::Hidden,
+          ..Default::default()
+        },
+      ));
 
-    let dist = 4;
+      chunks.insert(id, entity.id());
+    }
+  }
+}
 
-    for x in min.x-dist..max.x +dist{
-      for y in min.y-dist..max.y+dist {
-        let id = ChunkId(IVec2::new(x, y));
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-        _ = tracing::debug_span!("spawn", id = ?id).entered();
-        in_view.insert(id);
+  /// Gets the view of a camera centered on the given chunk seeing `size` chunks across.
+  fn view(center: Vec2, size: f32) -> Rect {
+    let chunk_size_sq = TileMapConf::default().chunk_size_sq();
+    let center = (center + 0.5) * chunk_size_sq;
 
-        if chunks.contains_key(&id) {
-          continue;
-        }
+    Rect::from_center_size(center, Vec2::splat(size * chunk_size_sq))
+  }
+
+  fn plan<I>(conf: &ChunkLoadConf, view: Rect, loaded: I) -> PresencePlan
+  where
+    I: IntoIterator<Item = ChunkId>,
+  {
+    plan_presence(conf, &TileMapConf::default(), view, loaded)
+  }
 
-        let entity = commands.spawn((
-          id,
-          ChunkTiles::default(),
-          MaterialMesh2dBundle {
-            mesh: mesh.clone().into(),
-            material: materials.add(ChunkMaterial::from_image(assets.load("sprites/tiles.png"))),
-            transform: Transform::from_xyz(x as f32 * plane.size.x, y as f32 * plane.size.y, 0.0),
-            ..Default::default()
-          },
-        ));
+  /// Runs [plan] until every chunk is spawned, returning the number of frames it took.
+  fn settle(conf: &ChunkLoadConf, view: Rect, loaded: &mut HashSet<ChunkId>) -> usize {
+    let mut frames = 0;
 
-        chunks.insert(id, entity.id());
+    loop {
+      let plan = plan(conf, view, loaded.iter().copied());
+      if plan.spawn.is_empty() && plan.despawn.is_empty() {
+        return frames;
       }
-    }
 
-    for (id, entity) in chunks.clone() {
-      if in_view.contains(&id) {
-        continue;
+      assert!(plan.spawn.len() <= conf.spawn_budget);
+
+      for id in plan.despawn {
+        loaded.remove(&id);
       }
+      loaded.extend(plan.spawn);
+      frames += 1;
+    }
+  }
 
-      chunks.remove(&id);
-      commands.entity(entity).despawn_recursive();
+  #[test]
+  fn validates_chunk_dimensions() {
+    let conf = TileMapConf::default();
+    assert_eq!(conf.validate(), Ok(()));
+
+    for conf in [
+      TileMapConf {
+        chunk_tiles_sq: 0,
+        ..conf
+      },
+      TileMapConf {
+        chunk_tiles_sq: 1 << 16,
+        ..conf
+      },
+      TileMapConf {
+        tile_size: 0.0,
+        ..conf
+      },
+      TileMapConf {
+        tile_size: f32::NAN,
+        ..conf
+      },
+    ] {
+      assert!(conf.validate().is_err(), "{conf:?}");
     }
   }
+
+  #[test]
+  fn spawns_nearest_chunks_first_within_budget() {
+    let conf = ChunkLoadConf {
+      load_radius: 1,
+      unload_radius: 2,
+      spawn_budget: 5,
+    };
+
+    let plan = plan(&conf, view(Vec2::ZERO, 0.5), []);
+
+    assert_eq!(
+      plan.spawn,
+      [
+        IVec2::new(0, 0),
+        IVec2::new(-1, 0),
+        IVec2::new(0, -1),
+        IVec2::new(0, 1),
+        IVec2::new(1, 0),
+      ]
+      .map(ChunkId)
+    );
+    assert!(plan.despawn.is_empty());
+
+    let mut loaded = HashSet::new();
+    assert_eq!(settle(&conf, view(Vec2::ZERO, 0.5), &mut loaded), 2);
+    assert_eq!(loaded.len(), 9);
+  }
+
+  #[test]
+  fn oscillating_camera_keeps_chunks() {
+    let conf = ChunkLoadConf::default();
+    let mut loaded = HashSet::new();
+
+    settle(&conf, view(Vec2::new(0.4, 0.0), 2.0), &mut loaded);
+    let initial = loaded.clone();
+
+    for x in [0.6, 0.4, 1.4, 0.4, 0.6] {
+      let plan = plan(&conf, view(Vec2::new(x, 0.0), 2.0), loaded.iter().copied());
+
+      assert!(plan.despawn.is_empty(), "despawned {:?} at {x}", plan.despawn);
+      loaded.extend(plan.spawn);
+    }
+
+    assert!(loaded.is_superset(&initial));
+  }
+
+  #[test]
+  fn distant_chunks_unload() {
+    let conf = ChunkLoadConf::default();
+    let mut loaded = HashSet::new();
+
+    settle(&conf, view(Vec2::ZERO, 2.0), &mut loaded);
+    settle(&conf, view(Vec2::new(20.0, 0.0), 2.0), &mut loaded);
+
+    let expected = plan(
+      &ChunkLoadConf {
+        spawn_budget: usize::MAX,
+        ..ChunkLoadConf::default()
+      },
+      view(Vec2::new(20.0, 0.0), 2.0),
+      [],
+    );
+
+    let mut loaded = loaded.into_iter().collect::<Vec<_>>();
+    let mut expected = expected.spawn;
+
+    loaded.sort_by_key(|id| (id.x, id.y));
+    expected.sort_by_key(|id| (id.x, id.y));
+
+    assert_eq!(loaded, expected);
+  }
 }
 
@@ -1,38 +1,247 @@
-use super::{CHUNK_TILES, CHUNK_TILES_SQ};
 use bevy::prelude::*;
+use std::error::Error;
+use std::fmt;
 
-#[repr(transparent)]
-#[derive(Debug, Clone, Copy, Deref, DerefMut)]
-pub struct TileArray<T>([T; CHUNK_TILES]);
+/// The tiles of a square chunk of `dim` tiles along each axis.
+///
+/// Tiles are stored row-major, the tile at `(x, y)` being at index `y * dim + x`, so iterating
+/// rows of tiles walks memory in order. [super::render::ChunkMaterial] uploads the tiles in this
+/// order and `tiles.wgsl` indexes them the same way, so the layout must be kept in sync with the
+/// shader.
+#[derive(Debug, Clone, PartialEq, Eq, Deref, DerefMut)]
+pub struct TileArray<T> {
+  dim: u32,
+  #[deref]
+  tiles: Box<[T]>,
+}
 
 impl<T> TileArray<T> {
+  /// Gets the number of tiles along each axis.
+  pub fn dim(&self) -> u32 {
+    self.dim
+  }
+
+  /// Gets whether the given position is within the array.
+  pub fn contains(&self, pos: UVec2) -> bool {
+    pos.cmplt(UVec2::splat(self.dim)).all()
+  }
+
+  /// Gets the tile at the given position, or nothing if it's outside of the array.
   pub fn get(&self, pos: UVec2) -> Option<&T> {
-    self.0.get(index(pos))
+    self.tiles.get(self.index(pos)?)
   }
 
+  /// Gets the tile at the given position mutably, or nothing if it's outside of the array.
   pub fn get_mut(&mut self, pos: UVec2) -> Option<&mut T> {
-    self.0.get_mut(index(pos))
+    let index = self.index(pos)?;
+    self.tiles.get_mut(index)
   }
 
+  /// Sets the tile at the given position.
+  ///
+  /// Positions outside of the array are a bug in the caller, so they panic in debug builds and
+  /// are ignored otherwise. Use [TileArray::try_set] when the position may be out of range.
   pub fn set(&mut self, pos: UVec2, tile: T) {
-    if let Some(x) = self.get_mut(pos) {
-      *x = tile;
-    }
+    let result = self.try_set(pos, tile);
+    debug_assert!(result.is_ok(), "{}", result.unwrap_err());
+  }
+
+  /// Sets the tile at the given position, failing if it's outside of the array.
+  pub fn try_set(&mut self, pos: UVec2, tile: T) -> Result<(), OutOfBounds> {
+    let dim = self.dim;
+    let current = self.get_mut(pos).ok_or(OutOfBounds { pos, dim })?;
+    *current = tile;
+
+    Ok(())
+  }
+
+  /// Iterates over the tiles along with their position, row by row.
+  pub fn iter_coords(&self) -> impl Iterator<Item = (UVec2, &T)> {
+    let dim = self.dim;
+
+    self
+      .tiles
+      .iter()
+      .enumerate()
+      .map(move |(i, tile)| (coords(dim, i), tile))
+  }
+
+  /// Iterates mutably over the tiles along with their position, row by row.
+  pub fn iter_mut_coords(&mut self) -> impl Iterator<Item = (UVec2, &mut T)> {
+    let dim = self.dim;
+
+    self
+      .tiles
+      .iter_mut()
+      .enumerate()
+      .map(move |(i, tile)| (coords(dim, i), tile))
+  }
+
+  /// Gets the tiles of row `y`.
+  pub fn row(&self, y: u32) -> &[T] {
+    let start = y as usize * self.dim as usize;
+    &self.tiles[start..start + self.dim as usize]
+  }
+
+  /// Gets the tiles of row `y` mutably.
+  pub fn row_mut(&mut self, y: u32) -> &mut [T] {
+    let start = y as usize * self.dim as usize;
+    &mut self.tiles[start..start + self.dim as usize]
+  }
+
+  /// Iterates over the tiles from `min` to `max` inclusive, row by row.
+  ///
+  /// The rectangle is clipped to the array, so it may be partially or entirely outside of it.
+  pub fn rect(&self, min: UVec2, max: UVec2) -> impl Iterator<Item = (UVec2, &T)> {
+    let max = max.min(UVec2::splat(self.dim.saturating_sub(1)));
+    let empty = self.dim == 0 || min.cmpgt(max).any();
+    let xs = min.x as usize..=max.x as usize;
+
+    (min.y..=max.y).filter(move |_| !empty).flat_map(move |y| {
+      self.row(y)[xs.clone()]
+        .iter()
+        .enumerate()
+        .map(move |(x, tile)| (UVec2::new(min.x + x as u32, y), tile))
+    })
+  }
+
+  /// Iterates over the tiles within `radius` tiles of `center` along each axis, including
+  /// `center` itself, row by row.
+  pub fn window(&self, center: UVec2, radius: u32) -> impl Iterator<Item = (UVec2, &T)> {
+    self.rect(
+      center.saturating_sub(UVec2::splat(radius)),
+      center.saturating_add(UVec2::splat(radius)),
+    )
+  }
+
+  /// Gets the index of the given position, checking each axis so positions past the end of a
+  /// row don't alias the start of the next one.
+  fn index(&self, pos: UVec2) -> Option<usize> {
+    self
+      .contains(pos)
+      .then(|| pos.y as usize * self.dim as usize + pos.x as usize)
   }
 }
 
 impl<T: Copy> TileArray<T> {
-  pub fn of(tile: T) -> Self {
-    Self([tile; CHUNK_TILES])
+  /// Creates a [TileArray] of `dim` by `dim` tiles all set to `tile`.
+  pub fn of(dim: u32, tile: T) -> Self {
+    Self {
+      dim,
+      tiles: vec![tile; dim as usize * dim as usize].into_boxed_slice(),
+    }
   }
 }
 
-impl<T: Copy + Default> Default for TileArray<T> {
-  fn default() -> Self {
-    Self::of(T::default())
+/// A position outside of a [TileArray].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds {
+  pub pos: UVec2,
+  pub dim: u32,
+}
+
+impl fmt::Display for OutOfBounds {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "tile {} is outside of an array of {} by {} tiles",
+      self.pos, self.dim, self.dim
+    )
   }
 }
 
-fn index(pos: UVec2) -> usize {
-  pos.x as usize * CHUNK_TILES_SQ + pos.y as usize
+impl Error for OutOfBounds {}
+
+/// Gets the position of the tile at index `i` of an array of `dim` tiles along each axis.
+fn coords(dim: u32, i: usize) -> UVec2 {
+  UVec2::new((i % dim as usize) as u32, (i / dim as usize) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{OutOfBounds, TileArray};
+  use bevy::math::UVec2;
+
+  /// Creates an array whose tiles are their own position.
+  fn positions(dim: u32) -> TileArray<UVec2> {
+    let mut tiles = TileArray::of(dim, UVec2::ZERO);
+    for (pos, tile) in tiles.iter_mut_coords() {
+      *tile = pos;
+    }
+
+    tiles
+  }
+
+  #[test]
+  fn rows_are_contiguous() {
+    let tiles = positions(4);
+
+    assert_eq!(tiles[1], UVec2::new(1, 0));
+    assert_eq!(tiles[4], UVec2::new(0, 1));
+    assert_eq!(
+      tiles.row(2),
+      (0..4).map(|x| UVec2::new(x, 2)).collect::<Vec<_>>()
+    );
+
+    for (pos, tile) in tiles.iter_coords() {
+      assert_eq!(tiles.get(pos), Some(tile));
+      assert_eq!(pos, *tile);
+    }
+  }
+
+  #[test]
+  fn out_of_range_positions_dont_alias() {
+    let mut tiles = positions(50);
+
+    for pos in [
+      UVec2::new(60, 0),
+      UVec2::new(0, 60),
+      UVec2::new(50, 49),
+      UVec2::splat(50),
+    ] {
+      assert!(!tiles.contains(pos));
+      assert_eq!(tiles.get(pos), None);
+      assert_eq!(tiles.get_mut(pos), None);
+      assert_eq!(
+        tiles.try_set(pos, UVec2::ZERO),
+        Err(OutOfBounds { pos, dim: 50 })
+      );
+    }
+
+    // `(60, 0)` used to resolve to the index of `(10, 1)`.
+    assert_eq!(tiles.get(UVec2::new(10, 1)), Some(&UVec2::new(10, 1)));
+    assert_eq!(tiles, positions(50));
+
+    assert_eq!(tiles.try_set(UVec2::new(49, 49), UVec2::ZERO), Ok(()));
+    assert_eq!(tiles.get(UVec2::new(49, 49)), Some(&UVec2::ZERO));
+  }
+
+  #[test]
+  #[cfg(debug_assertions)]
+  #[should_panic(expected = "outside of an array")]
+  fn set_out_of_range_panics_in_debug() {
+    positions(4).set(UVec2::new(0, 4), UVec2::ZERO);
+  }
+
+  #[test]
+  fn rect_is_clipped() {
+    let tiles = positions(4);
+    let rect = |min: (u32, u32), max: (u32, u32)| {
+      tiles
+        .rect(min.into(), max.into())
+        .map(|(pos, tile)| {
+          assert_eq!(pos, *tile);
+          pos.into()
+        })
+        .collect::<Vec<(u32, u32)>>()
+    };
+
+    assert_eq!(rect((1, 2), (2, 3)), [(1, 2), (2, 2), (1, 3), (2, 3)]);
+    assert_eq!(rect((3, 3), (10, 10)), [(3, 3)]);
+    assert_eq!(rect((2, 0), (1, 0)), []);
+    assert_eq!(rect((5, 5), (6, 6)), []);
+
+    assert_eq!(tiles.window(UVec2::ZERO, 1).count(), 4);
+    assert_eq!(tiles.window(UVec2::ONE, 1).count(), 9);
+  }
 }
@@ -1,79 +1,361 @@
 use super::array::TileArray;
+use super::codec;
+use super::data::TileDataRegistry;
 use super::generate::{self, GenerateConf};
-use super::neighbors::Neighbors;
-use super::CHUNK_SIZE_SQ;
+use super::neighbors::{MooreNeighbors, Neighbors};
+use super::save::ChunkStore;
+use super::TileMapConf;
 use crate::sprites::Tiles;
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::HashSet;
+use futures_lite::future;
 use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// The number of tiles outside of a chunk read by [autotile].
+///
+/// Border tiles pick their transition from normalized neighbors, which in turn depend on their
+/// own neighbors, so two rings of tiles are needed.
+const AUTOTILE_PAD: i32 = 2;
 
 #[derive(Default, Deref, DerefMut, Resource)]
 pub struct Chunks(HashMap<ChunkId, Entity, fxhash::FxBuildHasher>);
 
+/// The coordinate of a chunk, see [super::coords] for conversions.
 #[derive(Debug, Clone, Copy, Deref, DerefMut, PartialEq, Eq, Hash, Component)]
 pub struct ChunkId(pub IVec2);
 
-impl ChunkId {
-  pub fn from_world(coord: Vec2) -> Self {
-    Self((coord / CHUNK_SIZE_SQ).as_ivec2())
+impl From<IVec2> for ChunkId {
+  fn from(value: IVec2) -> Self {
+    Self(value)
   }
+}
 
-  pub fn to_world(self) -> Vec2 {
-    (self.0.as_vec2() * CHUNK_SIZE_SQ) - (CHUNK_SIZE_SQ / 2.0)
-  }
+/// A layer of tiles within a chunk, drawn bottom to top.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileLayer {
+  /// The terrain, the only layer that is generated and autotiled.
+  #[default]
+  Ground,
+  /// Roads, trees and other decorations on top of the terrain.
+  Decoration,
+  /// Walls and other structures on top of everything else.
+  Structure,
+}
 
-  pub fn to_world_rect(self) -> Rect {
-    let min = self.to_world();
-    let max = min + CHUNK_SIZE_SQ;
+impl TileLayer {
+  /// Every layer in draw order.
+  pub const ALL: [Self; 3] = [Self::Ground, Self::Decoration, Self::Structure];
+  /// The number of layers.
+  pub const COUNT: usize = Self::ALL.len();
 
-    Rect::from_corners(min, max)
+  /// Gets the index of the layer in draw order.
+  pub fn index(self) -> usize {
+    self as usize
   }
 }
 
-impl From<IVec2> for ChunkId {
-  fn from(value: IVec2) -> Self {
-    Self(value)
+/// The tiles of every [TileLayer] of a chunk.
+///
+/// [Tiles::Void] is drawn on the ground layer but leaves the layers above it empty.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct ChunkTiles([TileArray<Tiles>; TileLayer::COUNT]);
+
+/// Marks a chunk whose tiles were edited so they're kept in the [ChunkStore] when it unloads.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct ChunkDirty;
+
+impl ChunkTiles {
+  /// Creates a chunk of void tiles of the given dimensions.
+  pub fn new(conf: &TileMapConf) -> Self {
+    Self(std::array::from_fn(|_| {
+      TileArray::of(conf.chunk_tiles_sq, Tiles::Void)
+    }))
+  }
+
+  /// Gets the tiles of the given layer.
+  pub fn layer(&self, layer: TileLayer) -> &TileArray<Tiles> {
+    &self.0[layer.index()]
+  }
+
+  /// Gets the tiles of the given layer mutably.
+  pub fn layer_mut(&mut self, layer: TileLayer) -> &mut TileArray<Tiles> {
+    &mut self.0[layer.index()]
+  }
+
+  /// Encodes the tiles of each layer in draw order with [codec::encode].
+  pub fn encode(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for layer in &self.0 {
+      codec::encode(&mut bytes, layer).expect("writing to a Vec can't fail");
+    }
+
+    bytes
   }
-}
 
-#[derive(Deref, DerefMut, Component)]
-pub struct ChunkTiles(TileArray<Tiles>);
+  /// Decodes tiles encoded with [ChunkTiles::encode] into a chunk of the given dimensions.
+  pub fn decode<R: Read>(mut r: R, conf: &TileMapConf) -> io::Result<Self> {
+    let mut tiles = Self::new(conf);
+    for layer in &mut tiles.0 {
+      *layer = codec::decode(&mut r, conf.chunk_tiles_sq)?;
+    }
 
-impl Default for ChunkTiles {
-  fn default() -> Self {
-    Self(TileArray::of(Tiles::Void))
+    Ok(tiles)
   }
 }
 
+/// A chunk whose tiles aren't ready yet.
+///
+/// Chunks are spawned with it and no task, so they're never mistaken for loaded chunks of
+/// [Tiles::Void]. [spawn] then removes it from chunks it restores from the [ChunkStore] and
+/// gives the others a task generating their tiles on the [AsyncComputeTaskPool].
+///
+/// The chunk is hidden until [poll_generating] fills in its tiles. Dropping the task cancels it,
+/// so a chunk that leaves view before its tiles are ready stops generating once despawned.
+#[derive(Component, Default)]
+pub struct ChunkGenerating(Option<Task<ChunkTiles>>);
+
+/// Fills spawned chunks with their stored tiles or starts generating them when they haven't
+/// been stored.
 #[tracing::instrument(skip_all)]
 pub fn spawn(
   conf: Res<GenerateConf>,
-  mut chunks: Query<(&ChunkId, &mut ChunkTiles), Added<ChunkId>>,
+  map: Res<TileMapConf>,
+  registry: Res<TileDataRegistry>,
+  mut store: ResMut<ChunkStore>,
+  mut commands: Commands,
+  mut chunks: Query<
+    (
+      Entity,
+      &ChunkId,
+      &mut ChunkTiles,
+      &mut ChunkGenerating,
+      &mut Visibility,
+    ),
+    Added<ChunkId>,
+  >,
 ) {
-  for (id, mut tiles) in &mut chunks {
+  let pool = AsyncComputeTaskPool::get();
+
+  for (entity, id, mut tiles, mut generating, mut visibility) in &mut chunks {
     _ = tracing::debug_span!("chunk", id = ?id).entered();
 
-    generate::generate(&conf, *id, &mut tiles);
+    let stored = match store.get(*id, &map) {
+      Some(Ok(stored)) => {
+        *tiles = stored.tiles;
+        *visibility = Visibility::Inherited;
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<ChunkGenerating>();
+        registry.insert(&mut entity, &stored.data, &conf, &map, *id);
+        continue;
+      }
+      Some(Err(err)) => {
+        error!("failed to restore chunk {id:?}, regenerating it: {err}");
+        HashMap::new()
+      }
+      None => HashMap::new(),
+    };
+
+    // Data layers are cheap next to the tiles so they're generated right away.
+    registry.insert(&mut commands.entity(entity), &stored, &conf, &map, *id);
+
+    let conf = conf.clone();
+    let map = *map;
+    let id = *id;
+    let task = pool.spawn(async move {
+      let mut tiles = ChunkTiles::new(&map);
+      generate::generate(&conf, id, &mut tiles);
+
+      tiles
+    });
+
+    generating.0 = Some(task);
+  }
+}
+
+/// Fills in the tiles of chunks once they are generated and shows them.
+#[tracing::instrument(skip_all)]
+pub fn poll_generating(
+  mut commands: Commands,
+  mut chunks: Query<(
+    Entity,
+    &mut ChunkGenerating,
+    &mut ChunkTiles,
+    &mut Visibility,
+  )>,
+) {
+  for (entity, mut generating, mut tiles, mut visibility) in &mut chunks {
+    let Some(task) = &mut generating.0 else {
+      continue;
+    };
+    let Some(generated) = future::block_on(future::poll_once(task)) else {
+      continue;
+    };
+
+    *tiles = generated;
+    *visibility = Visibility::Inherited;
+
+    commands.entity(entity).remove::<ChunkGenerating>();
+  }
+}
+
+/// Autotiles the ground of changed chunks so terrain borders use the matching transition tiles.
+///
+/// The chunks around a changed chunk are autotiled as well since their border tiles may now see
+/// different neighbors. Autotiling is idempotent, so a chunk is only marked as changed when its
+/// tiles actually differ. Chunks that are still generating are skipped and treated as unloaded.
+///
+/// Chunks rewritten around an edited chunk are marked with [ChunkDirty] too, so their new
+/// borders are stored along with the edit.
+#[tracing::instrument(skip_all)]
+pub fn cleanup(
+  map: Res<TileMapConf>,
+  chunks: Res<Chunks>,
+  mut commands: Commands,
+  mut query: Query<(&ChunkId, &mut ChunkTiles, Has<ChunkDirty>), Without<ChunkGenerating>>,
+) {
+  let mut dirty = HashSet::new();
+  let mut edited = HashSet::new();
+
+  for (id, tiles, is_dirty) in &mut query {
+    if !tiles.is_changed() {
+      continue;
+    }
+
+    for x in -1..=1 {
+      for y in -1..=1 {
+        let id = ChunkId(id.0 + IVec2::new(x, y));
+
+        dirty.insert(id);
+        if is_dirty {
+          edited.insert(id);
+        }
+      }
+    }
+  }
+
+  let get = |pos: IVec2| {
+    let entity = chunks.get(&ChunkId::from_tile(pos, &map))?;
+    let (_, tiles, _) = query.get(*entity).ok()?;
+
+    let ground = tiles.layer(TileLayer::Ground);
+
+    ground.get(ChunkId::to_local_tile(pos, &map)).copied()
+  };
+
+  let updates = dirty
+    .into_iter()
+    .filter_map(|id| {
+      _ = tracing::debug_span!("chunk", id = ?id).entered();
+
+      let entity = *chunks.get(&id)?;
+      let (_, current, _) = query.get(entity).ok()?;
+      let current = current.layer(TileLayer::Ground);
+      let tiles = autotile(&map, id, current, get);
+
+      (tiles != *current).then_some((id, entity, tiles))
+    })
+    .collect::<Vec<_>>();
+
+  for (id, entity, tiles) in updates {
+    if let Ok((_, mut current, is_dirty)) = query.get_mut(entity) {
+      *current.layer_mut(TileLayer::Ground) = tiles;
+
+      if !is_dirty && edited.contains(&id) {
+        commands.entity(entity).insert(ChunkDirty);
+      }
+    }
+  }
+}
+
+/// Autotiles the ground tiles of chunk `id`, resolving tiles in the surrounding chunks with `get`.
+///
+/// Tiles are first normalized into their terrain category (stray land becomes water, deep water
+/// next to land becomes shallow) and transitions are then picked from the normalized neighbors
+/// using the wang sets of the tile set.
+/// Neither step reads its own output, so a single pass converges.
+fn autotile<F>(
+  conf: &TileMapConf,
+  id: ChunkId,
+  tiles: &TileArray<Tiles>,
+  get: F,
+) -> TileArray<Tiles>
+where
+  F: Fn(IVec2) -> Option<Tiles>,
+{
+  let origin = id.to_tile(conf);
+  let dim = conf.chunk_tiles_sq as i32;
+
+  let raw = TileWindow::new(origin - AUTOTILE_PAD, dim + AUTOTILE_PAD * 2, |pos| {
+    if ChunkId::from_tile(pos, conf) == id {
+      tiles.get(ChunkId::to_local_tile(pos, conf)).copied()
+    } else {
+      get(pos)
+    }
+  });
+
+  let normalized = TileWindow::new(origin - 1, dim + 2, |pos| {
+    let mut tile = raw.get(pos)?;
+    let neighbors = Neighbors::from_fn(pos, |pos| raw.get(pos));
+
+    match tile.is_water() {
+      true => cleanup_water(&mut tile, &neighbors),
+      false => cleanup_land(&mut tile, &neighbors),
+    }
+
+    Some(tile)
+  });
+
+  let mut autotiled = tiles.clone();
+
+  for (local, tile) in autotiled.iter_mut_coords() {
+    let pos = origin + local.as_ivec2();
+    let Some(normalized_tile) = normalized.get(pos) else {
+      continue;
+    };
+
+    let neighbors = MooreNeighbors::from_fn(pos, |pos| normalized.get(pos));
+
+    *tile = normalized_tile;
+    cleanup_transition(tile, &neighbors);
   }
+
+  autotiled
 }
 
-pub fn cleanup(mut _tiles: Query<&mut ChunkTiles, Changed<ChunkTiles>>) {
-  // for mut tiles in &mut tiles {
-  //   for _ in 0..3 {
-  //     for x in 0..CHUNK_TILES_SQ as _ {
-  //       for y in 0..CHUNK_TILES_SQ as _ {
-  //         let id = UVec2::new(x, y);
-  //         let neighbors = Neighbors::from_chunk_tiles(id, &tiles);
-
-  //         match tiles.get_mut(id) {
-  //           Some(tile) if tile.is_sand() => cleanup_sand(tile, &neighbors),
-  //           Some(tile) if tile.is_grass() => cleanup_grass(tile, &neighbors),
-  //           Some(tile) if tile.is_water() => cleanup_water(tile, &neighbors),
-  //           _ => {}
-  //         };
-  //       }
-  //     }
-  //   }
-  // }
+/// A square window of global tile coordinates used while autotiling.
+struct TileWindow {
+  min: IVec2,
+  dim: i32,
+  tiles: Vec<Option<Tiles>>,
+}
+
+impl TileWindow {
+  fn new<F>(min: IVec2, dim: i32, mut f: F) -> Self
+  where
+    F: FnMut(IVec2) -> Option<Tiles>,
+  {
+    let mut tiles = Vec::with_capacity((dim * dim) as usize);
+
+    for y in 0..dim {
+      for x in 0..dim {
+        tiles.push(f(min + IVec2::new(x, y)));
+      }
+    }
+
+    Self { min, dim, tiles }
+  }
+
+  fn get(&self, pos: IVec2) -> Option<Tiles> {
+    let pos = pos - self.min;
+    if pos.cmplt(IVec2::ZERO).any() || pos.cmpge(IVec2::splat(self.dim)).any() {
+      return None;
+    }
+
+    self.tiles[(pos.y * self.dim + pos.x) as usize]
+  }
 }
 
 fn cleanup_water(tile: &mut Tiles, neighbors: &Neighbors<Tiles>) {
@@ -84,43 +366,360 @@ fn cleanup_water(tile: &mut Tiles, neighbors: &Neighbors<Tiles>) {
   }
 }
 
-fn cleanup_sand(tile: &mut Tiles, neighbors: &Neighbors<Tiles>) {
+fn cleanup_land(tile: &mut Tiles, neighbors: &Neighbors<Tiles>) {
   let water = neighbors.map(|t| t.is_water()).filter(|t| *t);
-  if water.is_empty() {
-    return;
-  }
 
   if water.len() == 4 {
     *tile = Tiles::WaterShallow;
-    return;
   }
+}
 
-  if water.north == Some(true) {
-    if water.east == Some(true) {
-      *tile = Tiles::BeachTopRight;
-    } else if water.west == Some(true) {
-      *tile = Tiles::BeachTopLeft;
-    } else {
-      *tile = Tiles::BeachTop;
+/// Picks the transition tile from the wang set the tile belongs to.
+fn cleanup_transition(tile: &mut Tiles, neighbors: &MooreNeighbors<Tiles>) {
+  if let Some(set) = Tiles::WANG_SETS.iter().find(|set| set.contains(tile)) {
+    *tile = set.autotile(*tile, neighbors.into_clockwise());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{autotile, cleanup, poll_generating, spawn, ChunkDirty, ChunkGenerating};
+  use super::{ChunkId, ChunkTiles, Chunks, TileLayer};
+  use crate::sprites::Tiles;
+  use crate::tilemap::array::TileArray;
+  use crate::tilemap::data::TileDataRegistry;
+  use crate::tilemap::generate::GenerateConf;
+  use crate::tilemap::save::ChunkStore;
+  use crate::tilemap::TileMapConf;
+  use bevy::ecs::system::RunSystemOnce;
+  use bevy::math::{IVec2, UVec2};
+  use bevy::prelude::*;
+  use bevy::tasks::{AsyncComputeTaskPool, TaskPool};
+  use std::time::{Duration, Instant};
+
+  const CENTER: IVec2 = IVec2::new(10, 10);
+
+  /// Autotiles chunk `0, 0` of the world described by `world`.
+  fn autotile_world<F>(world: F) -> TileArray<Tiles>
+  where
+    F: Fn(IVec2) -> Tiles,
+  {
+    let conf = TileMapConf::default();
+    let mut tiles = TileArray::of(conf.chunk_tiles_sq, Tiles::Void);
+    for (pos, tile) in tiles.iter_mut_coords() {
+      *tile = world(pos.as_ivec2());
     }
-  } else if water.south == Some(true) {
-    if water.east == Some(true) {
-      *tile = Tiles::BeachBottomLeft;
-    } else if water.west == Some(true) {
-      *tile = Tiles::BeachBottomRight;
-    } else {
-      *tile = Tiles::BeachBottom;
+
+    autotile(&conf, ChunkId(IVec2::ZERO), &tiles, |pos| Some(world(pos)))
+  }
+
+  /// Autotiles a world of `land` with water at the given offsets from [CENTER].
+  fn autotile_center(land: Tiles, water: &[(i32, i32)]) -> Tiles {
+    let tiles = autotile_world(|pos| {
+      if water.contains(&(pos - CENTER).into()) {
+        Tiles::WaterShallow
+      } else {
+        land
+      }
+    });
+
+    *tiles.get(CENTER.as_uvec2()).unwrap()
+  }
+
+  #[test]
+  fn sand_transitions() {
+    let cases = [
+      (&[(0, 1)][..], Tiles::BeachTop),
+      (&[(0, -1)], Tiles::BeachBottom),
+      (&[(1, 0)], Tiles::BeachRight),
+      (&[(-1, 0)], Tiles::BeachLeft),
+      (&[(0, 1), (1, 0)], Tiles::BeachTopRight),
+      (&[(0, 1), (-1, 0)], Tiles::BeachTopLeft),
+      (&[(0, -1), (1, 0)], Tiles::BeachBottomRight),
+      (&[(0, -1), (-1, 0)], Tiles::BeachBottomLeft),
+      (&[(1, 1)], Tiles::Beach),
+      (&[], Tiles::Beach),
+    ];
+
+    for (water, expected) in cases {
+      assert_eq!(autotile_center(Tiles::Beach, water), expected, "{water:?}");
+      assert_eq!(
+        autotile_center(Tiles::BeachTop, water),
+        expected,
+        "{water:?}"
+      );
     }
-  } else if water.east == Some(true) {
-    *tile = Tiles::BeachLeft;
-  } else if water.west == Some(true) {
-    *tile = Tiles::BeachRight;
   }
-}
 
-fn cleanup_grass(tile: &mut Tiles, neighbors: &Neighbors<Tiles>) {
-  let water = neighbors.map(|t| t.is_water()).filter(|t| *t);
-  if water.len() == 4 {
-    *tile = Tiles::WaterShallow;
+  #[test]
+  fn grass_transitions() {
+    let cases = [
+      (&[(0, 1)][..], Tiles::GrassWaterNorth),
+      (&[(0, -1)], Tiles::GrassWaterSouth),
+      (&[(1, 0)], Tiles::GrassWaterEast),
+      (&[(-1, 0)], Tiles::GrassWaterWest),
+      (&[(0, 1), (1, 0)], Tiles::GrassWaterNorthEast),
+      (&[(0, 1), (-1, 0)], Tiles::GrassWaterNorthWest),
+      (&[(0, -1), (1, 0)], Tiles::GrassWaterSouthEast),
+      (&[(0, -1), (-1, 0)], Tiles::GrassWaterSouthWest),
+      (&[(1, 1)], Tiles::GrassWaterNorthAndEast),
+      (&[(-1, 1)], Tiles::GrassWaterNorthAndWest),
+      (&[(1, -1)], Tiles::GrassWaterSouthAndEast),
+      (&[(-1, -1)], Tiles::GrassWaterSouthAndWest),
+      (&[], Tiles::Grass),
+    ];
+
+    for (water, expected) in cases {
+      assert_eq!(autotile_center(Tiles::Grass, water), expected, "{water:?}");
+    }
+
+    assert_eq!(autotile_center(Tiles::Grass2, &[]), Tiles::Grass2);
+    assert_eq!(autotile_center(Tiles::GrassWaterNorth, &[]), Tiles::Grass);
+  }
+
+  #[test]
+  fn land_surrounded_by_water_becomes_water() {
+    let tiles = autotile_world(|pos| match pos == CENTER {
+      true => Tiles::Grass,
+      false => Tiles::WaterShallow,
+    });
+
+    assert_eq!(tiles.get(CENTER.as_uvec2()), Some(&Tiles::WaterShallow));
+  }
+
+  #[test]
+  fn deep_water_next_to_land_becomes_shallow() {
+    let tiles = autotile_world(|pos| match pos.x < CENTER.x {
+      true => Tiles::Grass,
+      false => Tiles::WaterDeep,
+    });
+
+    assert_eq!(tiles.get(CENTER.as_uvec2()), Some(&Tiles::WaterShallow));
+    assert_eq!(
+      tiles.get((CENTER + IVec2::X).as_uvec2()),
+      Some(&Tiles::WaterDeep)
+    );
+    assert_eq!(
+      tiles.get((CENTER - IVec2::X).as_uvec2()),
+      Some(&Tiles::GrassWaterEast)
+    );
+  }
+
+  #[test]
+  fn transitions_across_chunk_borders() {
+    let dim = TileMapConf::default().chunk_tiles_sq as i32;
+    let tiles = autotile_world(|pos| match pos.x >= dim || pos.y < 0 {
+      true => Tiles::WaterShallow,
+      false => Tiles::Grass,
+    });
+
+    let last = dim as u32 - 1;
+
+    assert_eq!(
+      tiles.get(UVec2::new(last, 10)),
+      Some(&Tiles::GrassWaterEast)
+    );
+    assert_eq!(tiles.get(UVec2::new(10, 0)), Some(&Tiles::GrassWaterSouth));
+    assert_eq!(
+      tiles.get(UVec2::new(last, 0)),
+      Some(&Tiles::GrassWaterSouthEast)
+    );
+  }
+
+  #[test]
+  fn single_pass_converges() {
+    let conf = TileMapConf::default();
+    let center = IVec2::splat(conf.chunk_tiles_sq as i32 / 2);
+    let world = |pos: IVec2| {
+      let distance = pos.distance_squared(center);
+      let noise = (pos.x * 7 + pos.y * 13) % 5;
+
+      match distance + noise {
+        d if d < 64 => Tiles::Grass,
+        d if d < 100 => Tiles::Beach,
+        d if d < 200 => Tiles::WaterShallow,
+        _ => Tiles::WaterDeep,
+      }
+    };
+
+    let once = autotile_world(world);
+    let twice = autotile(&conf, ChunkId(IVec2::ZERO), &once, |pos| Some(world(pos)));
+
+    assert!(once == twice);
+  }
+
+  /// Spawns chunks `(0, 0)` and `(1, 0)` of grass with water along the east border of the first,
+  /// returning the grass chunk.
+  fn shore_world(world: &mut World, edited: bool) -> Entity {
+    let conf = TileMapConf::default();
+    let mut chunks = Chunks::default();
+
+    let mut shore = ChunkTiles::new(&conf);
+    let dim = conf.chunk_tiles_sq;
+    for (pos, tile) in shore.layer_mut(TileLayer::Ground).iter_mut_coords() {
+      *tile = match pos.x >= dim - 3 {
+        true => Tiles::WaterShallow,
+        false => Tiles::Grass,
+      };
+    }
+
+    let mut grass = ChunkTiles::new(&conf);
+    *grass.layer_mut(TileLayer::Ground) = TileArray::of(dim, Tiles::Grass);
+
+    let id = ChunkId(IVec2::ZERO);
+    let mut entity = world.spawn((id, shore));
+    if edited {
+      entity.insert(ChunkDirty);
+    }
+    chunks.insert(id, entity.id());
+
+    let id = ChunkId(IVec2::X);
+    let grass = world.spawn((id, grass)).id();
+    chunks.insert(id, grass);
+
+    world.insert_resource(chunks);
+    world.insert_resource(conf);
+    grass
+  }
+
+  #[test]
+  fn cleanup_marks_neighbors_of_edits_dirty() {
+    let mut world = World::new();
+    let grass = shore_world(&mut world, true);
+
+    world.run_system_once(cleanup);
+
+    let tiles = world.get::<ChunkTiles>(grass).unwrap();
+    assert_eq!(
+      tiles.layer(TileLayer::Ground).get(UVec2::new(0, 10)),
+      Some(&Tiles::GrassWaterWest)
+    );
+    assert!(world.get::<ChunkDirty>(grass).is_some());
+  }
+
+  #[test]
+  fn cleanup_keeps_neighbors_of_generated_chunks_clean() {
+    let mut world = World::new();
+    let grass = shore_world(&mut world, false);
+
+    world.run_system_once(cleanup);
+
+    let tiles = world.get::<ChunkTiles>(grass).unwrap();
+    assert_eq!(
+      tiles.layer(TileLayer::Ground).get(UVec2::new(0, 10)),
+      Some(&Tiles::GrassWaterWest)
+    );
+    assert!(world.get::<ChunkDirty>(grass).is_none());
+  }
+
+  #[test]
+  fn cleanup_skips_chunks_waiting_to_generate() {
+    let conf = TileMapConf::default();
+    let mut world = World::new();
+    let mut chunks = Chunks::default();
+
+    let mut water = ChunkTiles::new(&conf);
+    *water.layer_mut(TileLayer::Ground) = TileArray::of(conf.chunk_tiles_sq, Tiles::WaterDeep);
+
+    let id = ChunkId(IVec2::ZERO);
+    let water = world.spawn((id, water)).id();
+    chunks.insert(id, water);
+
+    // A chunk spawned this frame is all void, which would count as land.
+    let id = ChunkId(IVec2::X);
+    let pending = (id, ChunkTiles::new(&conf), ChunkGenerating::default());
+    chunks.insert(id, world.spawn(pending).id());
+
+    world.insert_resource(chunks);
+    world.insert_resource(conf);
+    world.run_system_once(cleanup);
+
+    let tiles = world.get::<ChunkTiles>(water).unwrap();
+    assert!(tiles
+      .layer(TileLayer::Ground)
+      .iter()
+      .all(|tile| *tile == Tiles::WaterDeep));
+  }
+
+  #[test]
+  fn generated_coasts_are_autotiled() {
+    AsyncComputeTaskPool::get_or_init(TaskPool::default);
+
+    let map = TileMapConf::default();
+    let mut world = World::new();
+    world.insert_resource(map);
+    world.insert_resource(GenerateConf::default());
+    world.init_resource::<TileDataRegistry>();
+    world.init_resource::<ChunkStore>();
+
+    let mut chunks = Chunks::default();
+    for x in -3..=3 {
+      for y in -3..=3 {
+        let id = ChunkId(IVec2::new(x, y));
+        let entity = world.spawn((
+          id,
+          ChunkTiles::new(&map),
+          ChunkGenerating::default(),
+          Visibility::Hidden,
+        ));
+        chunks.insert(id, entity.id());
+      }
+    }
+    world.insert_resource(chunks);
+
+    // Ordered as in the TileMapPlugin.
+    let mut schedule = Schedule::default();
+    schedule.add_systems((
+      spawn,
+      poll_generating.after(spawn),
+      apply_deferred.after(poll_generating).before(cleanup),
+      cleanup.after(poll_generating),
+    ));
+
+    let start = Instant::now();
+    loop {
+      schedule.run(&mut world);
+
+      let mut generating = world.query::<&ChunkGenerating>();
+      if generating.iter(&world).next().is_none() {
+        break;
+      }
+
+      assert!(
+        start.elapsed() < Duration::from_secs(30),
+        "chunks never generated"
+      );
+      std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let chunks = world.resource::<Chunks>();
+    let ground = |entity: Entity| {
+      let tiles = world.get::<ChunkTiles>(entity).unwrap();
+      tiles.layer(TileLayer::Ground)
+    };
+    let get = |pos: IVec2| {
+      let id = ChunkId::from_tile(pos, &map);
+      let ground = ground(*chunks.get(&id)?);
+      ground.get(ChunkId::to_local_tile(pos, &map)).copied()
+    };
+
+    let mut coasts = 0;
+    for x in -2..=2 {
+      for y in -2..=2 {
+        let id = ChunkId(IVec2::new(x, y));
+        let current = ground(chunks[&id]);
+
+        if current.iter().any(Tiles::is_land) && !current.iter().all(Tiles::is_land) {
+          coasts += 1;
+        }
+
+        assert!(
+          autotile(&map, id, current, get) == *current,
+          "chunk {id:?} wasn't autotiled"
+        );
+      }
+    }
+
+    assert!(coasts > 0, "no generated coast to autotile");
   }
 }
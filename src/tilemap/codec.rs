@@ -0,0 +1,377 @@
+//! A compact encoding of the tiles and data layers of a chunk.
+//!
+//! Chunks are encoded as a palette of the distinct tiles in the chunk followed by the palette
+//! index of each tile packed into as few bits as the palette needs. Generated chunks are mostly
+//! made of one or two tiles, so they shrink from 10KB of tile ids to a few hundred bytes, and a
+//! chunk of a single tile takes 5 bytes.
+//!
+//! Data layers hold arbitrary bits rather than a few distinct tiles, so they're delta and
+//! run-length encoded instead, see [encode_bits].
+use super::array::TileArray;
+use crate::sprites::Tiles;
+use std::io::{self, Read, Write};
+
+// The palette length is stored in a `u8`, which holds every tile of the tile set.
+const _: () = assert!(Tiles::ALL.len() <= u8::MAX as usize + 1);
+
+/// Writes the tiles of a chunk as a palette of stable tile ids and bit-packed palette indices.
+///
+/// The palette is its length minus one as a `u8` followed by each tile id as a little endian
+/// `u32`. Palette indices follow, least significant bit first, using `ceil(log2(len))` bits each.
+pub fn encode<W: Write>(mut w: W, tiles: &TileArray<Tiles>) -> io::Result<()> {
+  let mut palette = Vec::<Tiles>::new();
+  let mut indices = Vec::with_capacity(tiles.len());
+
+  for tile in tiles.iter() {
+    let index = match palette.iter().position(|entry| entry == tile) {
+      Some(index) => index,
+      None => {
+        palette.push(*tile);
+        palette.len() - 1
+      }
+    };
+
+    indices.push(index as u32);
+  }
+
+  w.write_all(&[(palette.len() - 1) as u8])?;
+  for tile in &palette {
+    w.write_all(&tile.id().to_le_bytes())?;
+  }
+
+  let bits = bits_for(palette.len());
+  if bits == 0 {
+    return Ok(());
+  }
+
+  let mut packed = vec![0u8; packed_len(tiles.len(), bits)];
+  for (i, index) in indices.into_iter().enumerate() {
+    for bit in 0..bits {
+      if index & (1 << bit) != 0 {
+        let at = i * bits + bit;
+        packed[at / 8] |= 1 << (at % 8);
+      }
+    }
+  }
+
+  w.write_all(&packed)
+}
+
+/// Reads the tiles of a chunk of `dim` by `dim` tiles written by [encode].
+pub fn decode<R: Read>(mut r: R, dim: u32) -> io::Result<TileArray<Tiles>> {
+  let mut len = [0u8; 1];
+  r.read_exact(&mut len)?;
+
+  let mut palette = Vec::with_capacity(len[0] as usize + 1);
+  for _ in 0..=len[0] {
+    let mut id = [0u8; 4];
+    r.read_exact(&mut id)?;
+
+    let id = u32::from_le_bytes(id);
+    let tile = Tiles::from_id(id).ok_or_else(|| invalid_data(format!("unknown tile id {id}")))?;
+
+    palette.push(tile);
+  }
+
+  let bits = bits_for(palette.len());
+  let mut tiles = TileArray::of(dim, palette[0]);
+  if bits == 0 {
+    return Ok(tiles);
+  }
+
+  let mut packed = vec![0u8; packed_len(tiles.len(), bits)];
+  r.read_exact(&mut packed)?;
+
+  for (i, tile) in tiles.iter_mut().enumerate() {
+    let mut index = 0usize;
+    for bit in 0..bits {
+      let at = i * bits + bit;
+      if packed[at / 8] & (1 << (at % 8)) != 0 {
+        index |= 1 << bit;
+      }
+    }
+
+    *tile = *palette
+      .get(index)
+      .ok_or_else(|| invalid_data(format!("palette index {index} out of bounds")))?;
+  }
+
+  Ok(tiles)
+}
+
+/// Writes the bits of the values of a data layer compressed for values that change gradually.
+///
+/// Each value is replaced by its wrapping difference from the previous value, and the bytes of
+/// the differences are split into four planes from the least significant byte up, each written
+/// with [pack_bits]. Gradual changes leave the upper planes nearly constant, so they collapse
+/// into a few runs, and a layer of a single value takes a few bytes per plane.
+pub fn encode_bits<W: Write>(mut w: W, values: &[u32]) -> io::Result<()> {
+  let mut previous = 0u32;
+  let deltas = values
+    .iter()
+    .map(|value| {
+      let delta = value.wrapping_sub(previous);
+      previous = *value;
+      delta
+    })
+    .collect::<Vec<_>>();
+
+  for plane in 0..4 {
+    let bytes = deltas
+      .iter()
+      .map(|delta| (delta >> (plane * 8)) as u8)
+      .collect::<Vec<_>>();
+
+    pack_bits(&mut w, &bytes)?;
+  }
+
+  Ok(())
+}
+
+/// Reads `len` values written by [encode_bits].
+pub fn decode_bits<R: Read>(mut r: R, len: usize) -> io::Result<Vec<u32>> {
+  let mut deltas = vec![0u32; len];
+
+  for plane in 0..4 {
+    let bytes = unpack_bits(&mut r, len)?;
+
+    for (delta, byte) in deltas.iter_mut().zip(bytes) {
+      *delta |= (byte as u32) << (plane * 8);
+    }
+  }
+
+  let mut previous = 0u32;
+  Ok(
+    deltas
+      .into_iter()
+      .map(|delta| {
+        previous = previous.wrapping_add(delta);
+        previous
+      })
+      .collect(),
+  )
+}
+
+/// Writes bytes run-length encoded as in PackBits.
+///
+/// Every run starts with a header byte `n`. When `n < 128` it's followed by `n + 1` literal
+/// bytes, otherwise by a single byte repeated `n - 125` times.
+fn pack_bits<W: Write>(mut w: W, bytes: &[u8]) -> io::Result<()> {
+  let mut literals = Vec::new();
+  let flush = |w: &mut W, literals: &mut Vec<u8>| -> io::Result<()> {
+    for chunk in literals.chunks(128) {
+      w.write_all(&[(chunk.len() - 1) as u8])?;
+      w.write_all(chunk)?;
+    }
+
+    literals.clear();
+    Ok(())
+  };
+
+  let mut i = 0;
+  while i < bytes.len() {
+    let byte = bytes[i];
+    let run = bytes[i..]
+      .iter()
+      .take(130)
+      .take_while(|other| **other == byte)
+      .count();
+
+    if run < 3 {
+      literals.push(byte);
+      i += 1;
+      continue;
+    }
+
+    flush(&mut w, &mut literals)?;
+    w.write_all(&[(run + 125) as u8, byte])?;
+    i += run;
+  }
+
+  flush(&mut w, &mut literals)
+}
+
+/// Reads `len` bytes written by [pack_bits].
+fn unpack_bits<R: Read>(mut r: R, len: usize) -> io::Result<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(len);
+
+  while bytes.len() < len {
+    let mut header = [0u8; 1];
+    r.read_exact(&mut header)?;
+
+    let start = bytes.len();
+    match header[0] {
+      n @ 0..=127 => {
+        bytes.resize(start + n as usize + 1, 0);
+        r.read_exact(&mut bytes[start..])?;
+      }
+      n => {
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte)?;
+        bytes.resize(start + n as usize - 125, byte[0]);
+      }
+    }
+  }
+
+  if bytes.len() > len {
+    return Err(invalid_data(format!(
+      "expected {len} bytes, got a run to {}",
+      bytes.len()
+    )));
+  }
+
+  Ok(bytes)
+}
+
+/// Gets the number of bits needed to store an index into a palette of the given length.
+fn bits_for(len: usize) -> usize {
+  (usize::BITS - (len.max(1) - 1).leading_zeros()) as usize
+}
+
+/// Gets the number of bytes of the packed indices of a chunk of `len` tiles.
+fn packed_len(len: usize, bits: usize) -> usize {
+  (len * bits).div_ceil(8)
+}
+
+pub(super) fn invalid_data<E>(error: E) -> io::Error
+where
+  E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+  io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tilemap::chunk::{ChunkId, ChunkTiles, TileLayer};
+  use crate::tilemap::generate::{self, GenerateConf};
+  use crate::tilemap::TileMapConf;
+  use bevy::math::{IVec2, UVec2};
+
+  fn round_trip(tiles: &TileArray<Tiles>) -> usize {
+    let mut bytes = Vec::new();
+    encode(&mut bytes, tiles).unwrap();
+
+    assert_eq!(&decode(bytes.as_slice(), tiles.dim()).unwrap(), tiles);
+
+    bytes.len()
+  }
+
+  #[test]
+  fn round_trips() {
+    for dim in [32, 50, 64] {
+      assert_eq!(round_trip(&TileArray::of(dim, Tiles::WaterDeep)), 5);
+
+      let mut tiles = TileArray::of(dim, Tiles::Grass);
+      tiles.set(UVec2::new(0, dim - 1), Tiles::Beach);
+      assert_eq!(round_trip(&tiles), 1 + 2 * 4 + tiles.len().div_ceil(8));
+
+      for (i, tile) in tiles.iter_mut().enumerate() {
+        *tile = Tiles::ALL[i % Tiles::ALL.len()];
+      }
+      round_trip(&tiles);
+    }
+  }
+
+  #[test]
+  fn rejects_truncated_chunks() {
+    let mut tiles = TileArray::of(50, Tiles::Grass);
+    tiles.set(UVec2::ZERO, Tiles::Beach);
+
+    let mut bytes = Vec::new();
+    encode(&mut bytes, &tiles).unwrap();
+    bytes.pop();
+
+    let err = decode(bytes.as_slice(), tiles.dim()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  }
+
+  #[test]
+  fn rejects_unknown_tiles() {
+    let mut bytes = vec![1];
+    bytes.extend_from_slice(&Tiles::Grass.id().to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+
+    let err = decode(bytes.as_slice(), 50).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+  }
+
+  fn round_trip_bits(values: &[u32]) -> usize {
+    let mut bytes = Vec::new();
+    encode_bits(&mut bytes, values).unwrap();
+
+    let mut r = bytes.as_slice();
+    assert_eq!(decode_bits(&mut r, values.len()).unwrap(), values);
+    assert!(r.is_empty());
+
+    bytes.len()
+  }
+
+  #[test]
+  fn compresses_data_layers() {
+    assert_eq!(round_trip_bits(&[]), 0);
+    assert!(round_trip_bits(&[7; 2500]) < 200);
+
+    let smooth = (0..2500)
+      .map(|i| (0.5 + (i as f32 * 0.01).sin() * 0.25).to_bits())
+      .collect::<Vec<_>>();
+    assert!(round_trip_bits(&smooth) < smooth.len() * 3);
+
+    // Values that don't compress only pay for the literal headers.
+    let mut state = 0x2545f491u32;
+    let noise = (0..2500)
+      .map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+      })
+      .collect::<Vec<_>>();
+    assert!(round_trip_bits(&noise) <= noise.len() * 4 + 4 * 2500usize.div_ceil(128));
+
+    // Runs around the run and literal limits.
+    for len in [1, 2, 3, 4, 127, 128, 129, 130, 131, 260, 261] {
+      let mut values = vec![1; len];
+      values.extend((0..len as u32).map(|i| i.wrapping_mul(0x0101_0101)));
+      values.extend(vec![u32::MAX; len]);
+      round_trip_bits(&values);
+    }
+  }
+
+  #[test]
+  fn rejects_overlong_runs() {
+    let mut bytes = Vec::new();
+    encode_bits(&mut bytes, &[0; 10]).unwrap();
+
+    let err = decode_bits(bytes.as_slice(), 5).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let err = decode_bits(&bytes[..bytes.len() - 1], 10).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  }
+
+  /// Checks the size of the ground of generated chunks against the raw 4 bytes per tile.
+  #[test]
+  fn generated_chunks_are_small() {
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+    let raw = map.chunk_tiles() * std::mem::size_of::<u32>();
+
+    let mut sizes = Vec::new();
+    for cx in -8..8 {
+      for cy in -8..8 {
+        let mut tiles = ChunkTiles::new(&map);
+        generate::generate(&conf, ChunkId(IVec2::new(cx, cy)), &mut tiles);
+
+        sizes.push(round_trip(tiles.layer(TileLayer::Ground)));
+      }
+    }
+
+    let mean = sizes.iter().sum::<usize>() / sizes.len();
+    let max = *sizes.iter().max().unwrap();
+
+    // Mostly water or a single biome, with a few hundred bytes for mixed grass and coasts.
+    assert!(mean * 20 < raw, "mean of {mean} bytes per chunk");
+    assert!(max * 5 < raw, "max of {max} bytes per chunk");
+  }
+}
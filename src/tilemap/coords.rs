@@ -0,0 +1,240 @@
+//! Conversions between world, global tile and chunk coordinates.
+//!
+//! - World coordinates are in world units, with tile `(0, 0)` starting at the origin.
+//! - Global tile coordinates identify a tile across the whole map, tile `t` covering the world
+//!   from `t * tile_size` up to but excluding `(t + 1) * tile_size`.
+//! - Chunk coordinates identify a [ChunkId] covering `chunk_tiles_sq` tiles along each axis, and
+//!   local tile coordinates identify a tile within its chunk.
+//!
+//! Every conversion floors so negative coordinates belong to the tile or chunk below them rather
+//! than being rounded toward zero. Dimensions come from the [TileMapConf].
+use super::chunk::ChunkId;
+use super::TileMapConf;
+use bevy::prelude::*;
+
+/// A global tile coordinate, identifying a tile across the whole map.
+#[derive(Debug, Default, Clone, Copy, Deref, DerefMut, PartialEq, Eq, Hash)]
+pub struct TilePos(pub IVec2);
+
+impl TilePos {
+  pub const fn new(x: i32, y: i32) -> Self {
+    Self(IVec2::new(x, y))
+  }
+
+  /// Gets the tile containing the given world coordinate.
+  pub fn from_world(world: Vec2, conf: &TileMapConf) -> Self {
+    Self((world / conf.tile_size).floor().as_ivec2())
+  }
+
+  /// Gets the world coordinate of the min corner of the tile.
+  pub fn to_world(self, conf: &TileMapConf) -> Vec2 {
+    self.0.as_vec2() * conf.tile_size
+  }
+
+  /// Gets the world coordinate of the center of the tile.
+  pub fn center(self, conf: &TileMapConf) -> Vec2 {
+    self.to_world(conf) + conf.tile_size / 2.0
+  }
+
+  /// Gets the chunk containing the tile.
+  pub fn chunk(self, conf: &TileMapConf) -> ChunkId {
+    ChunkId::from_tile(self.0, conf)
+  }
+
+  /// Gets the tile coordinate local to its chunk.
+  pub fn local(self, conf: &TileMapConf) -> UVec2 {
+    ChunkId::to_local_tile(self.0, conf)
+  }
+}
+
+impl From<IVec2> for TilePos {
+  fn from(value: IVec2) -> Self {
+    Self(value)
+  }
+}
+
+impl ChunkId {
+  /// Gets the chunk containing the given world coordinate.
+  pub fn from_world(world: Vec2, conf: &TileMapConf) -> Self {
+    // Going through tiles keeps this consistent with [TilePos::from_world] near chunk borders.
+    TilePos::from_world(world, conf).chunk(conf)
+  }
+
+  /// Gets the world coordinate of the min corner of the chunk.
+  pub fn to_world(self, conf: &TileMapConf) -> Vec2 {
+    TilePos(self.to_tile(conf)).to_world(conf)
+  }
+
+  /// Gets the area of the world covered by the chunk.
+  pub fn to_world_rect(self, conf: &TileMapConf) -> Rect {
+    let min = self.to_world(conf);
+
+    Rect::from_corners(min, min + conf.chunk_size_sq())
+  }
+
+  /// Gets the chunk containing the given global tile coordinate.
+  pub fn from_tile(tile: IVec2, conf: &TileMapConf) -> Self {
+    Self(tile.div_euclid(IVec2::splat(conf.chunk_tiles_sq as _)))
+  }
+
+  /// Gets the global tile coordinate of the first tile within the chunk.
+  pub fn to_tile(self, conf: &TileMapConf) -> IVec2 {
+    self.0 * conf.chunk_tiles_sq as i32
+  }
+
+  /// Gets the tile coordinate local to its chunk for the given global tile coordinate.
+  pub fn to_local_tile(tile: IVec2, conf: &TileMapConf) -> UVec2 {
+    tile
+      .rem_euclid(IVec2::splat(conf.chunk_tiles_sq as _))
+      .as_uvec2()
+  }
+
+  /// Gets the global tile coordinate of the given tile local to the chunk.
+  pub fn to_global_tile(self, local: UVec2, conf: &TileMapConf) -> TilePos {
+    TilePos(self.to_tile(conf) + local.as_ivec2())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{Rng, SeedableRng};
+  use rand_xorshift::XorShiftRng;
+
+  const SAMPLES: usize = 10_000;
+
+  /// The default dimensions along with the ones we'd like to experiment with.
+  fn confs() -> [TileMapConf; 3] {
+    [
+      TileMapConf::default(),
+      TileMapConf {
+        chunk_tiles_sq: 32,
+        tile_size: 16.0,
+      },
+      TileMapConf {
+        chunk_tiles_sq: 64,
+        tile_size: 8.0,
+      },
+    ]
+  }
+
+  fn rng() -> XorShiftRng {
+    XorShiftRng::seed_from_u64(0x5eed)
+  }
+
+  fn world(rng: &mut XorShiftRng) -> Vec2 {
+    Vec2::new(rng.gen_range(-1e6..1e6), rng.gen_range(-1e6..1e6))
+  }
+
+  fn tile(rng: &mut XorShiftRng) -> TilePos {
+    TilePos::new(
+      rng.gen_range(-100_000..100_000),
+      rng.gen_range(-100_000..100_000),
+    )
+  }
+
+  #[test]
+  fn negative_coordinates_floor() {
+    for conf in &confs() {
+      let last = conf.chunk_tiles_sq - 1;
+
+      assert_eq!(
+        TilePos::from_world(Vec2::new(-0.5, -conf.tile_size), conf),
+        TilePos::new(-1, -1)
+      );
+      assert_eq!(
+        TilePos::from_world(Vec2::new(0.0, conf.tile_size - 0.5), conf),
+        TilePos::new(0, 0)
+      );
+
+      assert_eq!(
+        ChunkId::from_world(Vec2::new(-0.5, 0.5), conf),
+        ChunkId(IVec2::new(-1, 0))
+      );
+      assert_eq!(
+        ChunkId::from_world(Vec2::splat(-conf.chunk_size_sq()), conf),
+        ChunkId(IVec2::splat(-1))
+      );
+      assert_eq!(
+        ChunkId::from_tile(IVec2::new(-1, 0), conf),
+        ChunkId(IVec2::new(-1, 0))
+      );
+      assert_eq!(
+        ChunkId::to_local_tile(IVec2::new(-1, 0), conf),
+        UVec2::new(last, 0)
+      );
+    }
+  }
+
+  #[test]
+  fn world_tiles_contain_their_world_coordinates() {
+    for conf in &confs() {
+      let mut rng = rng();
+
+      for _ in 0..SAMPLES {
+        let world = world(&mut rng);
+        let tile = TilePos::from_world(world, conf);
+        let min = tile.to_world(conf);
+
+        assert!(
+          min.cmple(world).all() && world.cmplt(min + conf.tile_size).all(),
+          "{world} in {tile:?}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn tiles_round_trip_through_world() {
+    for conf in &confs() {
+      let mut rng = rng();
+
+      for _ in 0..SAMPLES {
+        let tile = tile(&mut rng);
+
+        assert_eq!(TilePos::from_world(tile.to_world(conf), conf), tile);
+        assert_eq!(TilePos::from_world(tile.center(conf), conf), tile);
+      }
+    }
+  }
+
+  #[test]
+  fn tiles_round_trip_through_chunks() {
+    for conf in &confs() {
+      let mut rng = rng();
+
+      for _ in 0..SAMPLES {
+        let tile = tile(&mut rng);
+        let chunk = tile.chunk(conf);
+        let local = tile.local(conf);
+
+        assert!(local.cmplt(UVec2::splat(conf.chunk_tiles_sq)).all());
+        assert_eq!(chunk.to_global_tile(local, conf), tile);
+      }
+    }
+  }
+
+  #[test]
+  fn world_chunks_agree_with_world_tiles() {
+    for conf in &confs() {
+      let mut rng = rng();
+
+      for _ in 0..SAMPLES {
+        let world = world(&mut rng);
+        let chunk = ChunkId::from_world(world, conf);
+
+        assert_eq!(
+          chunk,
+          TilePos::from_world(world, conf).chunk(conf),
+          "{world}"
+        );
+
+        let rect = chunk.to_world_rect(conf);
+        assert!(
+          rect.min.cmple(world).all() && world.cmplt(rect.max).all(),
+          "{world} in {rect:?}"
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,164 @@
+//! Per-tile data layers stored alongside the tiles of a chunk.
+//!
+//! A data layer is a [TileArray] of a [TileData] attached to every chunk as a [ChunkData]
+//! component. Layers are registered with [TileDataAppExt::add_tile_data], after which they are
+//! generated or restored when chunks spawn, stored with the chunk when it's edited and accessed
+//! by tile with [super::map::TileDataMap].
+use super::array::TileArray;
+use super::chunk::ChunkId;
+use super::codec::{self, invalid_data};
+use super::generate::GenerateConf;
+use super::TileMapConf;
+use bevy::ecs::system::EntityCommands;
+use bevy::ecs::world::EntityRef;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io;
+
+/// A value stored for every tile of a data layer.
+pub trait TileData: Copy + PartialEq + Send + Sync + 'static {
+  /// The name the layer is stored under, unique among the registered layers.
+  const NAME: &'static str;
+
+  /// Generates the values of a newly generated chunk.
+  fn generate(conf: &GenerateConf, map: &TileMapConf, id: ChunkId) -> TileArray<Self>;
+
+  /// Gets the value as the bits it's stored as.
+  fn to_bits(self) -> u32;
+
+  /// Gets the value from the bits written by [TileData::to_bits].
+  fn from_bits(bits: u32) -> Self;
+}
+
+/// The values of a data layer for the tiles of a chunk.
+#[derive(Debug, Clone, PartialEq, Deref, DerefMut, Component)]
+pub struct ChunkData<T: TileData>(pub TileArray<T>);
+
+/// The data layers registered with [TileDataAppExt::add_tile_data].
+#[derive(Default, Resource)]
+pub struct TileDataRegistry {
+  layers: Vec<TileDataLayer>,
+}
+
+/// The type erased operations of a registered data layer.
+struct TileDataLayer {
+  name: &'static str,
+  encode: fn(&EntityRef) -> Option<Vec<u8>>,
+  restore: fn(&mut EntityCommands, &[u8], &TileMapConf) -> io::Result<()>,
+  generate: fn(&mut EntityCommands, &GenerateConf, &TileMapConf, ChunkId),
+}
+
+impl TileDataRegistry {
+  /// Encodes the data layers of the given chunk entity by name.
+  pub fn encode(&self, entity: &EntityRef) -> Vec<(&'static str, Vec<u8>)> {
+    self
+      .layers
+      .iter()
+      .filter_map(|layer| Some((layer.name, (layer.encode)(entity)?)))
+      .collect()
+  }
+
+  /// Inserts the data layers of a chunk, restoring layers found in `stored` and generating the
+  /// others.
+  pub fn insert(
+    &self,
+    commands: &mut EntityCommands,
+    stored: &HashMap<String, Vec<u8>>,
+    conf: &GenerateConf,
+    map: &TileMapConf,
+    id: ChunkId,
+  ) {
+    for layer in &self.layers {
+      let Some(bytes) = stored.get(layer.name) else {
+        (layer.generate)(commands, conf, map, id);
+        continue;
+      };
+
+      if let Err(err) = (layer.restore)(commands, bytes, map) {
+        error!(
+          "failed to restore {} of chunk {id:?}, regenerating it: {err}",
+          layer.name
+        );
+        (layer.generate)(commands, conf, map, id);
+      }
+    }
+  }
+}
+
+/// Registers data layers on an [App].
+pub trait TileDataAppExt {
+  /// Registers the data layer of `T`.
+  ///
+  /// # Panics
+  ///
+  /// Panics when a layer with the same name is already registered, when the name is longer than
+  /// 255 bytes or when 255 layers are already registered, since chunks store both as a `u8`.
+  fn add_tile_data<T: TileData>(&mut self) -> &mut Self;
+}
+
+impl TileDataAppExt for App {
+  fn add_tile_data<T: TileData>(&mut self) -> &mut Self {
+    let mut registry = self
+      .world
+      .get_resource_or_insert_with(TileDataRegistry::default);
+
+    assert!(
+      registry.layers.iter().all(|layer| layer.name != T::NAME),
+      "tile data layer {} is already registered",
+      T::NAME
+    );
+    assert!(
+      T::NAME.len() <= u8::MAX as usize,
+      "tile data layer name {} is longer than 255 bytes",
+      T::NAME
+    );
+    assert!(
+      registry.layers.len() < u8::MAX as usize,
+      "can't register tile data layer {}, 255 layers are already registered",
+      T::NAME
+    );
+
+    registry.layers.push(TileDataLayer {
+      name: T::NAME,
+      encode: |entity| entity.get::<ChunkData<T>>().map(|data| encode(data)),
+      restore: |commands, bytes, map| {
+        commands.insert(ChunkData(decode::<T>(bytes, map.chunk_tiles_sq)?));
+        Ok(())
+      },
+      generate: |commands, conf, map, id| {
+        commands.insert(ChunkData(T::generate(conf, map, id)));
+      },
+    });
+
+    self
+  }
+}
+
+/// Encodes the bits of the values of a data layer with [codec::encode_bits].
+fn encode<T: TileData>(data: &TileArray<T>) -> Vec<u8> {
+  let bits = data.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+
+  let mut bytes = Vec::new();
+  codec::encode_bits(&mut bytes, &bits).expect("writing to a Vec can't fail");
+
+  bytes
+}
+
+/// Decodes the values of a data layer of `dim` by `dim` tiles written by [encode].
+fn decode<T: TileData>(mut bytes: &[u8], dim: u32) -> io::Result<TileArray<T>> {
+  let mut data = TileArray::of(dim, T::from_bits(0));
+  let bits = codec::decode_bits(&mut bytes, data.len())?;
+
+  if !bytes.is_empty() {
+    return Err(invalid_data(format!(
+      "{} bytes left after the tile data",
+      bytes.len()
+    )));
+  }
+
+  for (value, bits) in data.iter_mut().zip(bits) {
+    *value = T::from_bits(bits);
+  }
+
+  Ok(data)
+}
@@ -1,18 +1,20 @@
-use super::chunk::Chunks;
+use super::chunk::{ChunkId, Chunks};
+use super::TileMapConf;
 use crate::camera::GameCamera;
 use bevy::prelude::*;
 
 pub fn chunk_wireframes(
   mut gizmos: Gizmos,
+  conf: Res<TileMapConf>,
   chunks: Res<Chunks>,
   camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
   windows: Query<&Window>,
 ) {
   for chunk in chunks.keys() {
     gizmos.rect_2d(
-      chunk.to_world_rect().center(),
+      chunk.to_world_rect(&conf).center(),
       0.0,
-      chunk.to_world_rect().size(),
+      chunk.to_world_rect(&conf).size(),
       Color::BLUE,
     );
   }
@@ -24,15 +26,14 @@ pub fn chunk_wireframes(
     .filter_map(|coord| camera.viewport_to_world_2d(camera_transform, coord));
 
   for pos in cursors {
-    for chunk in chunks.keys() {
-      if chunk.to_world_rect().contains(pos) {
-        gizmos.rect_2d(
-          chunk.to_world_rect().center(),
-          0.0,
-          chunk.to_world_rect().size(),
-          Color::RED,
-        );
-      }
+    let chunk = ChunkId::from_world(pos, &conf);
+    if chunks.contains_key(&chunk) {
+      gizmos.rect_2d(
+        chunk.to_world_rect(&conf).center(),
+        0.0,
+        chunk.to_world_rect(&conf).size(),
+        Color::RED,
+      );
     }
   }
 }
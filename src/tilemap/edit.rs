@@ -1,5 +1,7 @@
-use super::chunk::{ChunkTiles, Chunks};
-use super::CHUNK_TILES_SQ;
+use super::chunk::TileLayer;
+use super::coords::TilePos;
+use super::generate::Fertility;
+use super::map::{TileDataMap, TileMap};
 use crate::camera::GameCamera;
 use crate::sprites::Tiles;
 use bevy::prelude::*;
@@ -10,6 +12,10 @@ use bevy_egui::{egui, EguiContexts};
 pub struct EditorConf {
   brush_size: f32,
   brush_tile: Tiles,
+  brush_layer: TileLayer,
+  /// Paints `brush_fertility` instead of tiles.
+  paint_fertility: bool,
+  brush_fertility: f32,
 }
 
 impl Default for EditorConf {
@@ -17,11 +23,27 @@ impl Default for EditorConf {
     Self {
       brush_size: 5.0,
       brush_tile: Tiles::Void,
+      brush_layer: TileLayer::Ground,
+      paint_fertility: false,
+      brush_fertility: 1.0,
     }
   }
 }
 
-pub fn menu(mut conf: ResMut<EditorConf>, mut egui: EguiContexts) {
+pub fn menu(
+  mut conf: ResMut<EditorConf>,
+  camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+  windows: Query<&Window>,
+  mut egui: EguiContexts,
+  fertility: TileDataMap<Fertility>,
+) {
+  let (camera, camera_transform) = camera.single();
+  let hovered = windows
+    .iter()
+    .filter_map(|window| window.cursor_position())
+    .filter_map(|coord| camera.viewport_to_world_2d(camera_transform, coord))
+    .find_map(|pos| fertility.get(TilePos::from_world(pos, fertility.conf())));
+
   egui::Window::new("editor")
     .anchor(Align2::RIGHT_TOP, (-10.0, 10.0))
     .title_bar(false)
@@ -35,6 +57,16 @@ pub fn menu(mut conf: ResMut<EditorConf>, mut egui: EguiContexts) {
           ui.add(egui::Slider::new(&mut conf.brush_size, 1.0..=50.0));
           ui.end_row();
 
+          ui.label("Brush layer");
+          egui::ComboBox::from_id_source("brush_layer")
+            .selected_text(format!("{:?}", conf.brush_layer))
+            .show_ui(ui, |ui| {
+              for layer in TileLayer::ALL {
+                ui.selectable_value(&mut conf.brush_layer, layer, format!("{:?}", layer));
+              }
+            });
+          ui.end_row();
+
           ui.label("Brush tile");
           egui::ComboBox::from_label("")
             .selected_text(format!("{:?}", conf.brush_tile))
@@ -46,17 +78,28 @@ pub fn menu(mut conf: ResMut<EditorConf>, mut egui: EguiContexts) {
               }
             });
           ui.end_row();
+
+          ui.checkbox(&mut conf.paint_fertility, "Paint fertility");
+          ui.add(egui::Slider::new(&mut conf.brush_fertility, 0.0..=1.0));
+          ui.end_row();
+
+          ui.label("Fertility");
+          match hovered {
+            Some(Fertility(value)) => ui.label(format!("{value:.2}")),
+            None => ui.label("-"),
+          };
+          ui.end_row();
         })
     });
 }
 
 pub fn draw(
   conf: Res<EditorConf>,
-  chunks: Res<Chunks>,
   camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
   windows: Query<&Window>,
   mut egui: EguiContexts,
-  mut tiles: Query<&mut ChunkTiles>,
+  mut map: TileMap,
+  mut fertility: TileDataMap<Fertility>,
 ) {
   if egui.ctx_mut().is_pointer_over_area() {
     return;
@@ -68,36 +111,28 @@ pub fn draw(
     .filter_map(|window| window.cursor_position())
     .filter_map(|coord| camera.viewport_to_world_2d(camera_transform, coord));
 
-  let brush_size = (conf.brush_size - 0.5) * 8.0f32;
-  let brush_size = brush_size.powf(2.0);
+  let tiles = *map.conf();
+  let radius = (conf.brush_size - 0.5) * tiles.tile_size;
+  let radius_sq = radius.powf(2.0);
 
   for cursor_pos in cursors {
-    let brush_rect = Rect::from_corners(cursor_pos - brush_size, cursor_pos + brush_size);
-
-    for (chunk_id, chunk) in chunks.iter() {
-      let chunk_rect = chunk_id.to_world_rect();
-      if chunk_rect.intersect(brush_rect).is_empty() {
-        continue;
-      }
-
-      let Ok(mut chunk) = tiles.get_mut(*chunk) else {
-        continue;
-      };
+    let min = TilePos::from_world(cursor_pos - radius, &tiles);
+    let max = TilePos::from_world(cursor_pos + radius, &tiles);
 
-      for x in 0..CHUNK_TILES_SQ as _ {
-        for y in 0..CHUNK_TILES_SQ as _ {
-          let tile_id = UVec2::new(x, y);
-          let tile_pos = UVec2::new(x, y).as_vec2() * 8.0 + chunk_id.to_world();
-          let tile_pos = tile_pos + 4.0;
+    let in_brush = |pos: TilePos| pos.center(&tiles).distance_squared(cursor_pos) <= radius_sq;
 
-          let distance = cursor_pos.distance_squared(tile_pos);
-          if distance > brush_size {
-            continue;
-          }
-
-          chunk.set(tile_id, conf.brush_tile);
+    if conf.paint_fertility {
+      fertility.edit(min, max, |pos, value| {
+        if in_brush(pos) {
+          *value = Fertility(conf.brush_fertility);
+        }
+      });
+    } else {
+      map.edit(conf.brush_layer, min, max, |pos, tile| {
+        if in_brush(pos) {
+          *tile = conf.brush_tile;
         }
-      }
+      });
     }
   }
 }
@@ -1,5 +1,7 @@
-use super::chunk::{ChunkId, ChunkTiles};
-use super::CHUNK_TILES_SQ;
+use super::array::TileArray;
+use super::chunk::{ChunkId, ChunkTiles, TileLayer};
+use super::data::TileData;
+use super::TileMapConf;
 use crate::sprites::Tiles;
 use bevy::math::DVec2;
 use bevy::prelude::*;
@@ -8,34 +10,138 @@ use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 
 /// Tile map generation configuration.
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct GenerateConf {
   /// The map seed.
   pub seed: u32,
   /// The continent scale defined as a value [0-1].
   pub continent_scale: f64,
+  /// The biome scale defined as a value [0-1], larger values making larger biomes.
+  pub biome_scale: f64,
+  /// The average temperature defined as a value [0-1], `0.5` being temperate.
+  pub temperature: f64,
+  /// The average moisture defined as a value [0-1], `0.5` being neither dry nor wet.
+  pub moisture: f64,
 }
 
+impl Default for GenerateConf {
+  fn default() -> Self {
+    Self {
+      seed: 0xdead,
+      continent_scale: 1.0,
+      biome_scale: 0.5,
+      temperature: 0.5,
+      moisture: 0.5,
+    }
+  }
+}
+
+/// The elevation at or below which land is beach rather than a biome.
+const SHORE: f64 = 0.25;
+
+/// The height above the shore below which land is always grassland, since grass is the only
+/// biome with transitions to beach and water.
+const COAST: f64 = 0.05;
+
+/// The land biomes picked from the elevation, temperature and moisture of a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+  Forest,
+  Grassland,
+  Plains,
+  Marsh,
+  Hills,
+  Mountains,
+  Desert,
+}
+
+impl Biome {
+  /// Picks the biome of a land tile, all values being within [0-1].
+  ///
+  /// `elevation` is the height above the shore, so the coast is grassland, high land becomes
+  /// hills and mountains regardless of climate and the rest is split by temperature and moisture.
+  pub fn from_climate(elevation: f64, temperature: f64, moisture: f64) -> Self {
+    match (elevation, temperature, moisture) {
+      (e, _, _) if e < COAST => Self::Grassland,
+      (e, _, _) if e > 0.7 => Self::Mountains,
+      (e, _, _) if e > 0.5 => Self::Hills,
+      (_, t, m) if t > 0.65 && m < 0.35 => Self::Desert,
+      (e, _, m) if e < 0.15 && m > 0.7 => Self::Marsh,
+      (_, _, m) if m > 0.55 => Self::Forest,
+      (_, _, m) if m > 0.35 => Self::Grassland,
+      _ => Self::Plains,
+    }
+  }
+
+  /// Gets a ground tile of the biome.
+  pub fn tile<R: Rng>(&self, rng: R) -> Tiles {
+    match self {
+      Self::Forest => Tiles::Forest,
+      Self::Grassland => Tiles::grass(rng),
+      Self::Plains => Tiles::Plains,
+      Self::Marsh => Tiles::Marsh,
+      Self::Hills => Tiles::Hills,
+      Self::Mountains => Tiles::Mountain,
+      Self::Desert => Tiles::Desert,
+    }
+  }
+}
+
+/// Generates the ground of the given chunk.
 pub fn generate(conf: &GenerateConf, chunk_id: ChunkId, chunk_tiles: &mut ChunkTiles) {
-  let dim = CHUNK_TILES_SQ as f64;
+  let ground = chunk_tiles.layer_mut(TileLayer::Ground);
+  let dim = ground.dim();
   let pos = chunk_id.as_dvec2();
 
   let mut rng = xor_shift_rng(conf, chunk_id.x, chunk_id.y);
   let continents = continents_noise(conf);
+  let temperature = climate_noise(conf, 2, conf.temperature);
+  let moisture = climate_noise(conf, 3, conf.moisture);
+
+  for (tile_pos, tile) in ground.iter_mut_coords() {
+    let p = pos * dim as f64 + tile_pos.as_dvec2();
+    *tile = match continents.get([p.x, p.y]) {
+      v if v <= 0.1 => Tiles::WaterDeep,
+      v if v <= 0.2 => Tiles::WaterShallow,
+      v if v <= SHORE => Tiles::Beach,
+      v => {
+        let elevation = (v - SHORE) / (1.0 - SHORE);
+        // High land is colder.
+        let temperature = temperature.get([p.x, p.y]) - elevation * 0.3;
+
+        Biome::from_climate(elevation, temperature, moisture.get([p.x, p.y])).tile(&mut rng)
+      }
+    };
+  }
+}
+
+/// How well plants grow on a tile as a value [0-1].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Fertility(pub f32);
+
+impl TileData for Fertility {
+  const NAME: &'static str = "fertility";
 
-  for x in 0..CHUNK_TILES_SQ as u32 {
-    for y in 0..CHUNK_TILES_SQ as u32 {
-      let px = pos.x * dim + x as f64;
-      let py = pos.y * dim + y as f64;
-      let tile = match continents.get([px, py]) {
-        v if v <= 0.1 => Tiles::WaterDeep,
-        v if v <= 0.2 => Tiles::WaterShallow,
-        v if v <= 0.25 => Tiles::Beach,
-        _ => Tiles::grass(&mut rng),
-      };
-
-      chunk_tiles.set(UVec2::new(x, y), tile);
+  fn generate(conf: &GenerateConf, map: &TileMapConf, id: ChunkId) -> TileArray<Self> {
+    let dim = map.chunk_tiles_sq;
+    let pos = id.as_dvec2() * dim as f64;
+    let noise = noise::ScalePoint::new(Perlin::new(conf.seed.wrapping_add(1))).set_scale(0.02);
+
+    let mut fertility = TileArray::of(dim, Fertility::default());
+    for (tile_pos, value) in fertility.iter_mut_coords() {
+      let p = pos + tile_pos.as_dvec2();
+      *value = Fertility((noise.get([p.x, p.y]) * 0.5 + 0.5).clamp(0.0, 1.0) as f32);
     }
+
+    fertility
+  }
+
+  fn to_bits(self) -> u32 {
+    self.0.to_bits()
+  }
+
+  fn from_bits(bits: u32) -> Self {
+    Self(f32::from_bits(bits))
   }
 }
 
@@ -89,6 +195,36 @@ fn continents_noise(conf: &GenerateConf) -> impl NoiseFn<f64, 2> {
     .set_y_scale(scale)
 }
 
+/// Gets a climate noise in the range [0-1] centered on `average`.
+///
+/// Each field uses its own seed offset from the map seed so temperature and moisture don't
+/// follow the continents or each other.
+fn climate_noise(conf: &GenerateConf, seed: u32, average: f64) -> impl NoiseFn<f64, 2> {
+  let biome_scale = conf.biome_scale.clamp(0.0, 1.0);
+
+  let scale_min = 0.02;
+  let scale_max = 0.002;
+  let scale = scale_min + (scale_max - scale_min) * biome_scale;
+
+  let climate = FbmOne {
+    source: Perlin::new(conf.seed.wrapping_add(seed)),
+    octaves: 4,
+    frequency: 1.0,
+    lacunarity: 2.0,
+    persistence: 0.5,
+  };
+
+  let climate = noise::ScaleBias::new(climate)
+    .set_scale(0.5)
+    .set_bias(average.clamp(0.0, 1.0));
+
+  let climate = noise::Clamp::new(climate)
+    .set_lower_bound(0.0)
+    .set_upper_bound(1.0);
+
+  noise::ScalePoint::new(climate).set_scale(scale)
+}
+
 struct FbmOne<T> {
   source: T,
   octaves: usize,
@@ -128,53 +264,139 @@ where
 
 #[cfg(test)]
 mod tests {
-  use super::GenerateConf;
+  use super::{Biome, GenerateConf};
   use crate::sprites::Tiles;
-  use crate::tilemap::chunk::ChunkTiles;
-  use crate::tilemap::CHUNK_TILES_SQ;
-  use bevy::math::{IVec2, UVec2};
+  use crate::tilemap::chunk::{ChunkTiles, TileLayer};
+  use crate::tilemap::TileMapConf;
+  use bevy::math::IVec2;
   use image::{Rgb, RgbImage};
 
+  /// The ground tiles of every biome but grassland.
+  const BIOME_TILES: [Tiles; 6] = [
+    Tiles::Forest,
+    Tiles::Plains,
+    Tiles::Marsh,
+    Tiles::Hills,
+    Tiles::Mountain,
+    Tiles::Desert,
+  ];
+
+  #[test]
+  fn biomes_follow_climate() {
+    let cases = [
+      ((0.9, 0.5, 0.5), Biome::Mountains),
+      ((0.6, 0.9, 0.1), Biome::Hills),
+      ((0.3, 0.9, 0.1), Biome::Desert),
+      ((0.1, 0.5, 0.9), Biome::Marsh),
+      ((0.3, 0.5, 0.9), Biome::Forest),
+      ((0.3, 0.5, 0.5), Biome::Grassland),
+      ((0.3, 0.2, 0.1), Biome::Plains),
+      ((0.01, 0.9, 0.1), Biome::Grassland),
+      ((0.01, 0.5, 0.9), Biome::Grassland),
+    ];
+
+    for ((elevation, temperature, moisture), biome) in cases {
+      assert_eq!(
+        Biome::from_climate(elevation, temperature, moisture),
+        biome,
+        "elevation {elevation}, temperature {temperature}, moisture {moisture}"
+      );
+    }
+  }
+
+  #[test]
+  fn generates_every_biome() {
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+    let mut seen = std::collections::HashSet::new();
+
+    for cx in -8..8 {
+      for cy in -8..8 {
+        let mut tiles = ChunkTiles::new(&map);
+        super::generate(&conf, IVec2::new(cx, cy).into(), &mut tiles);
+
+        seen.extend(tiles.layer(TileLayer::Ground).iter().copied());
+      }
+    }
+
+    for tile in [Tiles::Grass, Tiles::WaterDeep, Tiles::Beach]
+      .into_iter()
+      .chain(BIOME_TILES)
+    {
+      assert!(seen.contains(&tile), "{tile:?} wasn't generated");
+    }
+  }
+
+  #[test]
+  fn only_grass_meets_the_shore() {
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+    let shore = [Tiles::Beach, Tiles::WaterShallow, Tiles::WaterDeep];
+
+    for cx in -8..8 {
+      for cy in -8..8 {
+        let mut tiles = ChunkTiles::new(&map);
+        super::generate(&conf, IVec2::new(cx, cy).into(), &mut tiles);
+
+        let ground = tiles.layer(TileLayer::Ground);
+        for (pos, tile) in ground.iter_coords() {
+          if !BIOME_TILES.contains(tile) {
+            continue;
+          }
+
+          for (_, other) in ground.window(pos, 1) {
+            assert!(
+              !shore.contains(other),
+              "{tile:?} meets {other:?} near {pos} in chunk ({cx}, {cy})"
+            );
+          }
+        }
+      }
+    }
+  }
+
   #[test]
   #[ignore]
   fn generate_30x30_image() {
     let dim = 30u32;
-    let size = dim * CHUNK_TILES_SQ as u32;
-    let conf = GenerateConf {
-      seed: 0xdead,
-      continent_scale: 1.0,
-    };
+    let map = TileMapConf::default();
+    let size = dim * map.chunk_tiles_sq;
+    let conf = GenerateConf::default();
 
     let mut image = RgbImage::new(size, size);
 
     for cx in 0..dim {
       for cy in 0..dim {
-        let mut tiles = ChunkTiles::default();
+        let mut tiles = ChunkTiles::new(&map);
 
         super::generate(&conf, IVec2::new(cx as _, cy as _).into(), &mut tiles);
 
-        for x in 0..CHUNK_TILES_SQ as _ {
-          for y in 0..CHUNK_TILES_SQ as _ {
-            let tile = UVec2::new(x, y);
-            let tile = match tiles.get(tile).unwrap() {
-              Tiles::Grass => Rgb([0, 255, 0]),
-              Tiles::WaterDeep => Rgb([0, 0, 255]),
-              Tiles::WaterShallow => Rgb([100, 100, 255]),
-
-              Tiles::Void => Rgb([255, 0, 0]),
-
-              tile => {
-                println!("{tile:?}");
-                Rgb([0, 0, 0])
-              }
-            };
-
-            image.put_pixel(
-              cx * CHUNK_TILES_SQ as u32 + x,
-              cy * CHUNK_TILES_SQ as u32 + y,
-              tile,
-            );
-          }
+        for (pos, tile) in tiles.layer(TileLayer::Ground).iter_coords() {
+          let tile = match tile {
+            Tiles::Grass | Tiles::Grass1 | Tiles::Grass2 => Rgb([0, 255, 0]),
+            Tiles::WaterDeep => Rgb([0, 0, 255]),
+            Tiles::WaterShallow => Rgb([100, 100, 255]),
+            Tiles::Beach => Rgb([255, 230, 140]),
+            Tiles::Forest => Rgb([0, 120, 0]),
+            Tiles::Plains => Rgb([180, 220, 80]),
+            Tiles::Marsh => Rgb([80, 120, 90]),
+            Tiles::Hills => Rgb([150, 120, 70]),
+            Tiles::Mountain => Rgb([120, 120, 120]),
+            Tiles::Desert => Rgb([240, 200, 100]),
+
+            Tiles::Void => Rgb([255, 0, 0]),
+
+            tile => {
+              println!("{tile:?}");
+              Rgb([0, 0, 0])
+            }
+          };
+
+          image.put_pixel(
+            cx * map.chunk_tiles_sq + pos.x,
+            cy * map.chunk_tiles_sq + pos.y,
+            tile,
+          );
         }
       }
     }
@@ -0,0 +1,357 @@
+//! Access to the tiles of the map by global tile coordinate.
+use super::chunk::{ChunkDirty, ChunkGenerating, ChunkId, ChunkTiles, Chunks, TileLayer};
+use super::coords::TilePos;
+use super::data::{ChunkData, TileData};
+use super::TileMapConf;
+use crate::sprites::Tiles;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+/// The tiles of every loaded chunk, addressed by [TileLayer] and [TilePos].
+///
+/// Chunks that aren't loaded or are still generating are treated as missing, so reads return
+/// nothing and writes are dropped. Edited chunks are marked with [ChunkDirty].
+#[derive(SystemParam)]
+pub struct TileMap<'w, 's> {
+  conf: Res<'w, TileMapConf>,
+  chunks: Res<'w, Chunks>,
+  tiles: Query<'w, 's, &'static mut ChunkTiles, Without<ChunkGenerating>>,
+  commands: Commands<'w, 's>,
+}
+
+impl<'w, 's> TileMap<'w, 's> {
+  /// Gets the dimensions of the map.
+  pub fn conf(&self) -> &TileMapConf {
+    &self.conf
+  }
+
+  /// Gets the tile of the layer at the given position if its chunk is loaded.
+  pub fn get(&self, layer: TileLayer, pos: TilePos) -> Option<Tiles> {
+    let entity = self.chunks.get(&pos.chunk(&self.conf))?;
+    let tiles = self.tiles.get(*entity).ok()?;
+
+    tiles.layer(layer).get(pos.local(&self.conf)).copied()
+  }
+
+  /// Sets the tile of the layer at the given position, returning whether its chunk is loaded.
+  pub fn set(&mut self, layer: TileLayer, pos: TilePos, tile: Tiles) -> bool {
+    let mut loaded = false;
+
+    self.edit(layer, pos, pos, |_, current| {
+      *current = tile;
+      loaded = true;
+    });
+
+    loaded
+  }
+
+  /// Iterates over the loaded tiles of the layer from `min` to `max` inclusive, chunk by chunk.
+  pub fn region(
+    &self,
+    layer: TileLayer,
+    min: TilePos,
+    max: TilePos,
+  ) -> impl Iterator<Item = (TilePos, Tiles)> + '_ {
+    let conf = &*self.conf;
+
+    chunks_between(conf, min, max)
+      .filter_map(move |id| {
+        let entity = self.chunks.get(&id)?;
+        let tiles = self.tiles.get(*entity).ok()?;
+
+        Some((id, tiles.layer(layer)))
+      })
+      .flat_map(move |(id, tiles)| {
+        let (min, max) = local_bounds(conf, id, min, max);
+
+        tiles
+          .rect(min, max)
+          .map(move |(local, tile)| (id.to_global_tile(local, conf), *tile))
+      })
+  }
+
+  /// Calls `f` with every loaded tile of the layer from `min` to `max` inclusive, chunk by chunk.
+  ///
+  /// Each chunk is looked up once, and only chunks whose tiles `f` actually changed are marked
+  /// as changed and dirty.
+  pub fn edit<F>(&mut self, layer: TileLayer, min: TilePos, max: TilePos, mut f: F)
+  where
+    F: FnMut(TilePos, &mut Tiles),
+  {
+    let conf = *self.conf;
+
+    for id in chunks_between(&conf, min, max) {
+      let Some(entity) = self.chunks.get(&id).copied() else {
+        continue;
+      };
+      let Ok(mut tiles) = self.tiles.get_mut(entity) else {
+        continue;
+      };
+
+      let mut edited = false;
+
+      for local in locals_between(&conf, id, min, max) {
+        let current = *tiles.layer(layer).get(local).unwrap();
+        let mut tile = current;
+        f(id.to_global_tile(local, &conf), &mut tile);
+
+        if tile != current {
+          tiles.layer_mut(layer).set(local, tile);
+          edited = true;
+        }
+      }
+
+      if edited {
+        self.commands.entity(entity).insert(ChunkDirty);
+      }
+    }
+  }
+}
+
+/// The values of the data layer of `T` of every loaded chunk, addressed by [TilePos].
+///
+/// Like [TileMap], chunks that are still generating are treated as missing, since storing an
+/// edit to them would store their placeholder tiles as well. Edited chunks are marked with
+/// [ChunkDirty].
+#[derive(SystemParam)]
+pub struct TileDataMap<'w, 's, T: TileData> {
+  conf: Res<'w, TileMapConf>,
+  chunks: Res<'w, Chunks>,
+  data: Query<'w, 's, &'static mut ChunkData<T>, Without<ChunkGenerating>>,
+  commands: Commands<'w, 's>,
+}
+
+impl<'w, 's, T: TileData> TileDataMap<'w, 's, T> {
+  /// Gets the dimensions of the map.
+  pub fn conf(&self) -> &TileMapConf {
+    &self.conf
+  }
+
+  /// Gets the value at the given position if its chunk is loaded.
+  pub fn get(&self, pos: TilePos) -> Option<T> {
+    let entity = self.chunks.get(&pos.chunk(&self.conf))?;
+    let data = self.data.get(*entity).ok()?;
+
+    data.get(pos.local(&self.conf)).copied()
+  }
+
+  /// Calls `f` with every loaded value from `min` to `max` inclusive, chunk by chunk.
+  ///
+  /// Only chunks whose values `f` actually changed are marked as changed and dirty.
+  pub fn edit<F>(&mut self, min: TilePos, max: TilePos, mut f: F)
+  where
+    F: FnMut(TilePos, &mut T),
+  {
+    let conf = *self.conf;
+
+    for id in chunks_between(&conf, min, max) {
+      let Some(entity) = self.chunks.get(&id).copied() else {
+        continue;
+      };
+      let Ok(mut data) = self.data.get_mut(entity) else {
+        continue;
+      };
+
+      let mut edited = false;
+
+      for local in locals_between(&conf, id, min, max) {
+        let current = *data.get(local).unwrap();
+        let mut value = current;
+        f(id.to_global_tile(local, &conf), &mut value);
+
+        if value != current {
+          data.set(local, value);
+          edited = true;
+        }
+      }
+
+      if edited {
+        self.commands.entity(entity).insert(ChunkDirty);
+      }
+    }
+  }
+}
+
+/// Gets the chunks overlapping the tiles from `min` to `max` inclusive.
+fn chunks_between(conf: &TileMapConf, min: TilePos, max: TilePos) -> impl Iterator<Item = ChunkId> {
+  let (min, max) = (min.chunk(conf), max.chunk(conf));
+
+  (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| ChunkId(IVec2::new(x, y))))
+}
+
+/// Gets the bounds of the local tiles of chunk `id` from `min` to `max` inclusive.
+///
+/// The chunk must overlap the tiles, as it does for those of [chunks_between].
+fn local_bounds(conf: &TileMapConf, id: ChunkId, min: TilePos, max: TilePos) -> (UVec2, UVec2) {
+  let origin = id.to_tile(conf);
+  let min = (min.0 - origin).max(IVec2::ZERO);
+  let max = (max.0 - origin).min(IVec2::splat(conf.chunk_tiles_sq as i32 - 1));
+
+  (min.as_uvec2(), max.as_uvec2())
+}
+
+/// Gets the local tiles of chunk `id` from `min` to `max` inclusive, row by row.
+fn locals_between(
+  conf: &TileMapConf,
+  id: ChunkId,
+  min: TilePos,
+  max: TilePos,
+) -> impl Iterator<Item = UVec2> {
+  let (min, max) = local_bounds(conf, id, min, max);
+
+  (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| UVec2::new(x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tilemap::array::TileArray;
+  use crate::tilemap::generate::GenerateConf;
+  use bevy::ecs::system::SystemState;
+  use TileLayer::{Decoration, Ground};
+
+  /// Creates a world with chunks `(-1, 0)` and `(0, 0)` loaded.
+  fn world() -> World {
+    let mut world = World::new();
+    let mut chunks = Chunks::default();
+    let conf = TileMapConf::default();
+
+    for x in -1..=0 {
+      let id = ChunkId(IVec2::new(x, 0));
+      chunks.insert(id, world.spawn((id, ChunkTiles::new(&conf))).id());
+    }
+
+    world.insert_resource(chunks);
+    world.insert_resource(conf);
+    world
+  }
+
+  fn dirty(world: &mut World) -> Vec<ChunkId> {
+    let mut dirty = world
+      .query_filtered::<&ChunkId, With<ChunkDirty>>()
+      .iter(world)
+      .copied()
+      .collect::<Vec<_>>();
+
+    dirty.sort_by_key(|id| (id.x, id.y));
+    dirty
+  }
+
+  #[test]
+  fn edits_across_chunks() {
+    let mut world = world();
+    let mut state = SystemState::<TileMap>::new(&mut world);
+
+    let border = TilePos::new(-1, 3);
+    let mut map = state.get_mut(&mut world);
+
+    assert!(map.set(Ground, border, Tiles::Grass));
+    assert!(!map.set(Ground, TilePos::new(0, -1), Tiles::Grass));
+    let past = map.conf().chunk_tiles_sq as i32;
+    assert!(!map.set(Ground, TilePos::new(past, 0), Tiles::Grass));
+
+    map.edit(
+      Decoration,
+      TilePos::new(-2, 0),
+      TilePos::new(1, 1),
+      |_, tile| *tile = Tiles::Beach,
+    );
+    state.apply(&mut world);
+
+    let map = state.get_mut(&mut world);
+    assert_eq!(map.get(Ground, border), Some(Tiles::Grass));
+    assert_eq!(map.get(Decoration, border), Some(Tiles::Void));
+    assert_eq!(map.get(Ground, TilePos::new(-2, 1)), Some(Tiles::Void));
+    assert_eq!(map.get(Decoration, TilePos::new(-2, 1)), Some(Tiles::Beach));
+    assert_eq!(map.get(Decoration, TilePos::new(1, 0)), Some(Tiles::Beach));
+    assert_eq!(map.get(Decoration, TilePos::new(2, 0)), Some(Tiles::Void));
+    assert_eq!(map.get(Decoration, TilePos::new(0, -1)), None);
+
+    let painted = |layer| {
+      map
+        .region(layer, TilePos::new(-2, 0), TilePos::new(1, 3))
+        .filter(|(_, tile)| *tile != Tiles::Void)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(painted(Ground), [(border, Tiles::Grass)]);
+    assert_eq!(painted(Decoration).len(), 8);
+
+    assert_eq!(
+      dirty(&mut world),
+      [ChunkId(IVec2::new(-1, 0)), ChunkId(IVec2::ZERO)]
+    );
+  }
+
+  #[test]
+  fn edits_data_across_chunks() {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Height(u32);
+
+    impl TileData for Height {
+      const NAME: &'static str = "height";
+
+      fn generate(_: &GenerateConf, map: &TileMapConf, _: ChunkId) -> TileArray<Self> {
+        TileArray::of(map.chunk_tiles_sq, Height(0))
+      }
+
+      fn to_bits(self) -> u32 {
+        self.0
+      }
+
+      fn from_bits(bits: u32) -> Self {
+        Self(bits)
+      }
+    }
+
+    let mut world = world();
+    let conf = *world.resource::<TileMapConf>();
+    let ids = world
+      .resource::<Chunks>()
+      .values()
+      .copied()
+      .collect::<Vec<_>>();
+    for entity in ids {
+      world
+        .entity_mut(entity)
+        .insert(ChunkData(TileArray::of(conf.chunk_tiles_sq, Height(0))));
+    }
+
+    let mut state = SystemState::<TileDataMap<Height>>::new(&mut world);
+
+    let mut map = state.get_mut(&mut world);
+    map.edit(TilePos::new(-1, -1), TilePos::new(0, 0), |pos, h| {
+      h.0 = (pos.x + 2) as u32;
+    });
+    state.apply(&mut world);
+
+    let map = state.get_mut(&mut world);
+    assert_eq!(map.get(TilePos::new(-1, 0)), Some(Height(1)));
+    assert_eq!(map.get(TilePos::new(0, 0)), Some(Height(2)));
+    assert_eq!(map.get(TilePos::new(1, 0)), Some(Height(0)));
+    assert_eq!(map.get(TilePos::new(0, -1)), None);
+
+    assert_eq!(
+      dirty(&mut world),
+      [ChunkId(IVec2::new(-1, 0)), ChunkId(IVec2::ZERO)]
+    );
+  }
+
+  #[test]
+  fn unchanged_chunks_stay_clean() {
+    let mut world = world();
+    let mut state = SystemState::<TileMap>::new(&mut world);
+
+    let mut map = state.get_mut(&mut world);
+    map.set(Ground, TilePos::new(-1, 0), Tiles::Void);
+    map.edit(
+      Decoration,
+      TilePos::new(0, 0),
+      TilePos::new(10, 10),
+      |_, _| {},
+    );
+    state.apply(&mut world);
+
+    assert!(dirty(&mut world).is_empty());
+  }
+}
@@ -1,39 +1,109 @@
-use super::chunk::ChunkTiles;
+use super::array::TileArray;
+use super::chunk::TileLayer;
+use super::coords::TilePos;
+use super::map::TileMap;
 use crate::sprites::Tiles;
 use bevy::math::{IVec2, UVec2};
 use std::marker::PhantomData;
 use std::ptr::NonNull;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum NeighborDirection {
   North,
   East,
   West,
   South,
+  NorthEast,
+  NorthWest,
+  SouthEast,
+  SouthWest,
 }
 
 impl NeighborDirection {
   pub fn offset(&self) -> IVec2 {
     match self {
       Self::North => IVec2::new(0, 1),
-      Self::East => IVec2::new(-1, 0),
-      Self::West => IVec2::new(1, 0),
+      Self::East => IVec2::new(1, 0),
+      Self::West => IVec2::new(-1, 0),
       Self::South => IVec2::new(0, -1),
+      Self::NorthEast => IVec2::new(1, 1),
+      Self::NorthWest => IVec2::new(-1, 1),
+      Self::SouthEast => IVec2::new(1, -1),
+      Self::SouthWest => IVec2::new(-1, -1),
     }
   }
 
   pub fn offset_of(&self, of: UVec2) -> Option<UVec2> {
-    Some(match self {
-      Self::North => UVec2::new(of.x, of.y.checked_add(1)?),
-      Self::East => UVec2::new(of.x.checked_sub(1)?, of.y),
-      Self::West => UVec2::new(of.x.checked_add(1)?, of.y),
-      Self::South => UVec2::new(of.x, of.y.checked_sub(1)?),
-    })
+    let offset = self.offset();
+
+    Some(UVec2::new(
+      of.x.checked_add_signed(offset.x)?,
+      of.y.checked_add_signed(offset.y)?,
+    ))
+  }
+
+  /// Gets the bit of this direction within a neighbor bitmask.
+  ///
+  /// Bits are assigned clockwise starting at [NeighborDirection::North] so the mask of a
+  /// [MooreNeighbors] lines up with the order of a Tiled wang id.
+  pub fn bit(&self) -> u8 {
+    match self {
+      Self::North => 1 << 0,
+      Self::NorthEast => 1 << 1,
+      Self::East => 1 << 2,
+      Self::SouthEast => 1 << 3,
+      Self::South => 1 << 4,
+      Self::SouthWest => 1 << 5,
+      Self::West => 1 << 6,
+      Self::NorthWest => 1 << 7,
+    }
+  }
+
+  pub fn is_diagonal(&self) -> bool {
+    matches!(
+      self,
+      Self::NorthEast | Self::NorthWest | Self::SouthEast | Self::SouthWest
+    )
   }
 }
 
 impl NeighborDirection {
-  pub const CLOCKWISE: [Self; 4] = [Self::North, Self::West, Self::South, Self::East];
+  pub const CLOCKWISE: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+  pub const CLOCKWISE_MOORE: [Self; 8] = [
+    Self::North,
+    Self::NorthEast,
+    Self::East,
+    Self::SouthEast,
+    Self::South,
+    Self::SouthWest,
+    Self::West,
+    Self::NorthWest,
+  ];
+}
+
+/// A fixed set of optional neighbors addressable by [NeighborDirection].
+pub trait Neighborhood {
+  type Item;
+
+  /// The directions within the neighborhood in clockwise order.
+  const DIRECTIONS: &'static [NeighborDirection];
+
+  /// Gets the slot for the given direction if it's part of the neighborhood.
+  fn slot(&self, dir: NeighborDirection) -> Option<&Option<Self::Item>>;
+
+  /// Gets the mutable slot for the given direction if it's part of the neighborhood.
+  fn slot_mut(&mut self, dir: NeighborDirection) -> Option<&mut Option<Self::Item>>;
+
+  /// Gets a bitmask of the neighbors matching `f` using [NeighborDirection::bit].
+  fn mask<F>(&self, f: F) -> u8
+  where
+    F: Fn(&Self::Item) -> bool,
+  {
+    Self::DIRECTIONS
+      .iter()
+      .filter(|dir| matches!(self.slot(**dir), Some(Some(val)) if f(val)))
+      .fold(0, |mask, dir| mask | dir.bit())
+  }
 }
 
 #[derive(Clone, Copy)]
@@ -45,21 +115,33 @@ pub struct Neighbors<T> {
 }
 
 impl<T> Neighbors<T> {
-  pub fn get(&self, dir: NeighborDirection) -> Option<&T> {
-    match dir {
-      NeighborDirection::North => self.north.as_ref(),
-      NeighborDirection::East => self.east.as_ref(),
-      NeighborDirection::West => self.west.as_ref(),
-      NeighborDirection::South => self.south.as_ref(),
+  /// Collects the neighbors of the given global tile coordinate using `get` to resolve each
+  /// neighboring coordinate.
+  pub fn from_fn<F>(tile: IVec2, mut get: F) -> Self
+  where
+    F: FnMut(IVec2) -> Option<T>,
+  {
+    let mut neighbors = Self::default();
+
+    for dir in NeighborDirection::CLOCKWISE {
+      neighbors.set(dir, get(tile + dir.offset()));
     }
+
+    neighbors
   }
 
-  pub fn get_mut(&mut self, dir: NeighborDirection) -> &mut Option<T> {
-    match dir {
-      NeighborDirection::North => &mut self.north,
-      NeighborDirection::East => &mut self.east,
-      NeighborDirection::West => &mut self.west,
-      NeighborDirection::South => &mut self.south,
+  pub fn get(&self, dir: NeighborDirection) -> Option<&T> {
+    self.slot(dir)?.as_ref()
+  }
+
+  pub fn get_mut(&mut self, dir: NeighborDirection) -> Option<&mut T> {
+    self.slot_mut(dir)?.as_mut()
+  }
+
+  /// Sets the neighbor in the given direction, ignoring diagonal directions.
+  pub fn set(&mut self, dir: NeighborDirection, val: Option<T>) {
+    if let Some(slot) = self.slot_mut(dir) {
+      *slot = val;
     }
   }
 
@@ -95,40 +177,81 @@ impl<T> Neighbors<T> {
     self.len() == 0
   }
 
-  pub fn iter(&self) -> Iter<'_, T> {
-    Iter {
-      dir: 0,
-      neighbors: self,
+  pub fn iter(&self) -> Iter<'_, Self> {
+    Iter::new(self)
+  }
+
+  pub fn iter_mut(&mut self) -> IterMut<'_, Self> {
+    IterMut::new(self)
+  }
+
+  pub fn into_flat_array(self) -> Option<[T; 4]> {
+    Some([self.north?, self.east?, self.south?, self.west?])
+  }
+}
+
+impl Neighbors<bool> {
+  /// Gets the bitmask of the neighbors that are `true`.
+  pub fn to_bits(self) -> u8 {
+    self.mask(|val| *val)
+  }
+
+  /// Creates neighbors from a bitmask where every direction is present.
+  pub fn from_bits(bits: u8) -> Self {
+    let mut neighbors = Self::default();
+
+    for dir in NeighborDirection::CLOCKWISE {
+      neighbors.set(dir, Some(bits & dir.bit() != 0));
     }
+
+    neighbors
   }
+}
 
-  pub fn iter_mut(&mut self) -> IterMut<'_, T> {
-    IterMut {
-      dir: 0,
-      phantom: Default::default(),
-      neighbors: self.into(),
+impl<T> Neighborhood for Neighbors<T> {
+  type Item = T;
+
+  const DIRECTIONS: &'static [NeighborDirection] = &NeighborDirection::CLOCKWISE;
+
+  fn slot(&self, dir: NeighborDirection) -> Option<&Option<T>> {
+    match dir {
+      NeighborDirection::North => Some(&self.north),
+      NeighborDirection::East => Some(&self.east),
+      NeighborDirection::West => Some(&self.west),
+      NeighborDirection::South => Some(&self.south),
+      _ => None,
     }
   }
 
-  pub fn into_flat_array(self) -> Option<[T; 4]> {
-    Some([self.north?, self.west?, self.south?, self.east?])
+  fn slot_mut(&mut self, dir: NeighborDirection) -> Option<&mut Option<T>> {
+    match dir {
+      NeighborDirection::North => Some(&mut self.north),
+      NeighborDirection::East => Some(&mut self.east),
+      NeighborDirection::West => Some(&mut self.west),
+      NeighborDirection::South => Some(&mut self.south),
+      _ => None,
+    }
   }
 }
 
 impl Neighbors<Tiles> {
-  pub fn from_chunk_tiles(tile_id: UVec2, chunk_tiles: &ChunkTiles) -> Self {
+  pub fn from_chunk_tiles(tile_id: UVec2, chunk_tiles: &TileArray<Tiles>) -> Self {
     let mut neighbors = Self::default();
 
     for dir in NeighborDirection::CLOCKWISE {
       if let Some(id) = dir.offset_of(tile_id) {
-        if let Some(tile) = chunk_tiles.get(id) {
-          *neighbors.get_mut(dir) = Some(*tile);
-        }
+        neighbors.set(dir, chunk_tiles.get(id).copied());
       }
     }
 
     neighbors
   }
+
+  /// Gets the ground neighbors of the given tile, resolving tiles on a chunk border through the
+  /// adjacent chunks of the [TileMap].
+  pub fn from_world_tiles(tile: TilePos, map: &TileMap) -> Self {
+    Self::from_fn(tile.0, |pos| map.get(TileLayer::Ground, TilePos(pos)))
+  }
 }
 
 impl<T> Default for Neighbors<T> {
@@ -144,7 +267,7 @@ impl<T> Default for Neighbors<T> {
 
 impl<'a, T> IntoIterator for &'a Neighbors<T> {
   type Item = &'a T;
-  type IntoIter = Iter<'a, T>;
+  type IntoIter = Iter<'a, Neighbors<T>>;
 
   fn into_iter(self) -> Self::IntoIter {
     self.iter()
@@ -153,25 +276,253 @@ impl<'a, T> IntoIterator for &'a Neighbors<T> {
 
 impl<'a, T> IntoIterator for &'a mut Neighbors<T> {
   type Item = &'a mut T;
-  type IntoIter = IterMut<'a, T>;
+  type IntoIter = IterMut<'a, Neighbors<T>>;
 
   fn into_iter(self) -> Self::IntoIter {
     self.iter_mut()
   }
 }
 
-pub struct Iter<'a, T> {
-  dir: usize,
-  neighbors: &'a Neighbors<T>,
+/// An eight-way (Moore) neighborhood including the diagonal neighbors.
+#[derive(Clone, Copy)]
+pub struct MooreNeighbors<T> {
+  pub north: Option<T>,
+  pub north_east: Option<T>,
+  pub east: Option<T>,
+  pub south_east: Option<T>,
+  pub south: Option<T>,
+  pub south_west: Option<T>,
+  pub west: Option<T>,
+  pub north_west: Option<T>,
+}
+
+impl<T> MooreNeighbors<T> {
+  /// Collects the neighbors of the given global tile coordinate using `get` to resolve each
+  /// neighboring coordinate.
+  pub fn from_fn<F>(tile: IVec2, mut get: F) -> Self
+  where
+    F: FnMut(IVec2) -> Option<T>,
+  {
+    let mut neighbors = Self::default();
+
+    for dir in NeighborDirection::CLOCKWISE_MOORE {
+      neighbors.set(dir, get(tile + dir.offset()));
+    }
+
+    neighbors
+  }
+
+  pub fn get(&self, dir: NeighborDirection) -> Option<&T> {
+    self.slot(dir)?.as_ref()
+  }
+
+  pub fn get_mut(&mut self, dir: NeighborDirection) -> Option<&mut T> {
+    self.slot_mut(dir)?.as_mut()
+  }
+
+  pub fn set(&mut self, dir: NeighborDirection, val: Option<T>) {
+    if let Some(slot) = self.slot_mut(dir) {
+      *slot = val;
+    }
+  }
+
+  pub fn map<U, F>(self, f: F) -> MooreNeighbors<U>
+  where
+    F: FnOnce(T) -> U + Copy,
+  {
+    MooreNeighbors {
+      north: self.north.map(f),
+      north_east: self.north_east.map(f),
+      east: self.east.map(f),
+      south_east: self.south_east.map(f),
+      south: self.south.map(f),
+      south_west: self.south_west.map(f),
+      west: self.west.map(f),
+      north_west: self.north_west.map(f),
+    }
+  }
+
+  pub fn filter<F>(self, f: F) -> MooreNeighbors<T>
+  where
+    F: FnOnce(&T) -> bool + Copy,
+  {
+    MooreNeighbors {
+      north: self.north.filter(f),
+      north_east: self.north_east.filter(f),
+      east: self.east.filter(f),
+      south_east: self.south_east.filter(f),
+      south: self.south.filter(f),
+      south_west: self.south_west.filter(f),
+      west: self.west.filter(f),
+      north_west: self.north_west.filter(f),
+    }
+  }
+
+  /// Gets the neighbors clockwise starting at [NeighborDirection::North].
+  pub fn into_clockwise(self) -> [Option<T>; 8] {
+    [
+      self.north,
+      self.north_east,
+      self.east,
+      self.south_east,
+      self.south,
+      self.south_west,
+      self.west,
+      self.north_west,
+    ]
+  }
+
+  /// Gets the cardinal neighbors, dropping the diagonals.
+  pub fn cardinal(self) -> Neighbors<T> {
+    Neighbors {
+      north: self.north,
+      east: self.east,
+      west: self.west,
+      south: self.south,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.iter().count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn iter(&self) -> Iter<'_, Self> {
+    Iter::new(self)
+  }
+
+  pub fn iter_mut(&mut self) -> IterMut<'_, Self> {
+    IterMut::new(self)
+  }
 }
 
-impl<'a, T: 'a> Iterator for Iter<'a, T> {
+impl MooreNeighbors<bool> {
+  /// Gets the bitmask of the neighbors that are `true`.
+  pub fn to_bits(self) -> u8 {
+    self.mask(|val| *val)
+  }
+
+  /// Creates neighbors from a bitmask where every direction is present.
+  pub fn from_bits(bits: u8) -> Self {
+    let mut neighbors = Self::default();
+
+    for dir in NeighborDirection::CLOCKWISE_MOORE {
+      neighbors.set(dir, Some(bits & dir.bit() != 0));
+    }
+
+    neighbors
+  }
+}
+
+impl MooreNeighbors<Tiles> {
+  pub fn from_chunk_tiles(tile_id: UVec2, chunk_tiles: &TileArray<Tiles>) -> Self {
+    let mut neighbors = Self::default();
+
+    for dir in NeighborDirection::CLOCKWISE_MOORE {
+      if let Some(id) = dir.offset_of(tile_id) {
+        neighbors.set(dir, chunk_tiles.get(id).copied());
+      }
+    }
+
+    neighbors
+  }
+
+  /// Gets the ground neighbors of the given tile, resolving tiles on a chunk border through the
+  /// adjacent chunks of the [TileMap].
+  pub fn from_world_tiles(tile: TilePos, map: &TileMap) -> Self {
+    Self::from_fn(tile.0, |pos| map.get(TileLayer::Ground, TilePos(pos)))
+  }
+}
+
+impl<T> Neighborhood for MooreNeighbors<T> {
+  type Item = T;
+
+  const DIRECTIONS: &'static [NeighborDirection] = &NeighborDirection::CLOCKWISE_MOORE;
+
+  fn slot(&self, dir: NeighborDirection) -> Option<&Option<T>> {
+    Some(match dir {
+      NeighborDirection::North => &self.north,
+      NeighborDirection::NorthEast => &self.north_east,
+      NeighborDirection::East => &self.east,
+      NeighborDirection::SouthEast => &self.south_east,
+      NeighborDirection::South => &self.south,
+      NeighborDirection::SouthWest => &self.south_west,
+      NeighborDirection::West => &self.west,
+      NeighborDirection::NorthWest => &self.north_west,
+    })
+  }
+
+  fn slot_mut(&mut self, dir: NeighborDirection) -> Option<&mut Option<T>> {
+    Some(match dir {
+      NeighborDirection::North => &mut self.north,
+      NeighborDirection::NorthEast => &mut self.north_east,
+      NeighborDirection::East => &mut self.east,
+      NeighborDirection::SouthEast => &mut self.south_east,
+      NeighborDirection::South => &mut self.south,
+      NeighborDirection::SouthWest => &mut self.south_west,
+      NeighborDirection::West => &mut self.west,
+      NeighborDirection::NorthWest => &mut self.north_west,
+    })
+  }
+}
+
+impl<T> Default for MooreNeighbors<T> {
+  fn default() -> Self {
+    Self {
+      north: None,
+      north_east: None,
+      east: None,
+      south_east: None,
+      south: None,
+      south_west: None,
+      west: None,
+      north_west: None,
+    }
+  }
+}
+
+impl<'a, T> IntoIterator for &'a MooreNeighbors<T> {
   type Item = &'a T;
+  type IntoIter = Iter<'a, MooreNeighbors<T>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl<'a, T> IntoIterator for &'a mut MooreNeighbors<T> {
+  type Item = &'a mut T;
+  type IntoIter = IterMut<'a, MooreNeighbors<T>>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter_mut()
+  }
+}
+
+pub struct Iter<'a, N> {
+  dir: usize,
+  neighbors: &'a N,
+}
+
+impl<'a, N> Iter<'a, N> {
+  fn new(neighbors: &'a N) -> Self {
+    Self { dir: 0, neighbors }
+  }
+}
+
+impl<'a, N: Neighborhood> Iterator for Iter<'a, N>
+where
+  N::Item: 'a,
+{
+  type Item = &'a N::Item;
 
   fn next(&mut self) -> Option<Self::Item> {
     loop {
-      let dir = NeighborDirection::CLOCKWISE.get(self.dir)?;
-      let val = self.neighbors.get(*dir);
+      let dir = N::DIRECTIONS.get(self.dir)?;
+      let val = self.neighbors.slot(*dir).and_then(Option::as_ref);
 
       self.dir += 1;
 
@@ -182,26 +533,206 @@ impl<'a, T: 'a> Iterator for Iter<'a, T> {
   }
 }
 
-pub struct IterMut<'a, T> {
+pub struct IterMut<'a, N> {
   dir: usize,
-  phantom: PhantomData<&'a ()>,
-  neighbors: NonNull<Neighbors<T>>,
+  phantom: PhantomData<&'a mut N>,
+  neighbors: NonNull<N>,
 }
 
-impl<'a, T: 'a> Iterator for IterMut<'a, T> {
-  type Item = &'a mut T;
+impl<'a, N> IterMut<'a, N> {
+  fn new(neighbors: &'a mut N) -> Self {
+    Self {
+      dir: 0,
+      phantom: Default::default(),
+      neighbors: neighbors.into(),
+    }
+  }
+}
+
+impl<'a, N: Neighborhood> Iterator for IterMut<'a, N>
+where
+  N::Item: 'a,
+{
+  type Item = &'a mut N::Item;
 
   fn next(&mut self) -> Option<Self::Item> {
     loop {
-      let dir = NeighborDirection::CLOCKWISE.get(self.dir)?;
+      let dir = N::DIRECTIONS.get(self.dir)?;
       let val = unsafe { self.neighbors.as_mut() };
-      let val = val.get_mut(*dir);
+      let val = val.slot_mut(*dir).and_then(Option::as_mut);
 
       self.dir += 1;
 
       if val.is_some() {
-        return val.as_mut();
+        return val;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{MooreNeighbors, NeighborDirection, Neighbors};
+  use crate::sprites::Tiles;
+  use crate::tilemap::chunk::{ChunkId, ChunkTiles, Chunks, TileLayer};
+  use crate::tilemap::coords::TilePos;
+  use crate::tilemap::map::TileMap;
+  use crate::tilemap::TileMapConf;
+  use bevy::ecs::system::SystemState;
+  use bevy::math::{IVec2, UVec2};
+  use bevy::prelude::World;
+
+  #[test]
+  fn directions_follow_world_axes() {
+    let neighbors = Neighbors::from_fn(IVec2::ZERO, Some);
+
+    assert_eq!(neighbors.north, Some(IVec2::Y));
+    assert_eq!(neighbors.east, Some(IVec2::X));
+    assert_eq!(neighbors.south, Some(IVec2::NEG_Y));
+    assert_eq!(neighbors.west, Some(IVec2::NEG_X));
+
+    // Clockwise from north when looking at the map with +y up.
+    assert_eq!(
+      neighbors.into_flat_array(),
+      Some([IVec2::Y, IVec2::X, IVec2::NEG_Y, IVec2::NEG_X])
+    );
+
+    for dir in NeighborDirection::CLOCKWISE {
+      let of = UVec2::ONE;
+      assert_eq!(
+        dir.offset_of(of).map(|pos| pos.as_ivec2()),
+        Some(of.as_ivec2() + dir.offset())
+      );
+    }
+
+    assert_eq!(NeighborDirection::West.offset_of(UVec2::ZERO), None);
+    assert_eq!(NeighborDirection::South.offset_of(UVec2::ZERO), None);
+  }
+
+  #[test]
+  fn moore_offsets_go_clockwise() {
+    let neighbors = MooreNeighbors::from_fn(IVec2::ZERO, Some);
+
+    assert_eq!(neighbors.north, Some(IVec2::new(0, 1)));
+    assert_eq!(neighbors.north_east, Some(IVec2::new(1, 1)));
+    assert_eq!(neighbors.east, Some(IVec2::new(1, 0)));
+    assert_eq!(neighbors.south_east, Some(IVec2::new(1, -1)));
+    assert_eq!(neighbors.south, Some(IVec2::new(0, -1)));
+    assert_eq!(neighbors.south_west, Some(IVec2::new(-1, -1)));
+    assert_eq!(neighbors.west, Some(IVec2::new(-1, 0)));
+    assert_eq!(neighbors.north_west, Some(IVec2::new(-1, 1)));
+
+    for dir in NeighborDirection::CLOCKWISE_MOORE {
+      assert_eq!(
+        dir.is_diagonal(),
+        dir.offset().x != 0 && dir.offset().y != 0
+      );
+      assert_eq!(neighbors.get(dir), Some(&dir.offset()));
+      assert_eq!(
+        dir.offset_of(UVec2::ONE).map(|pos| pos.as_ivec2()),
+        Some(IVec2::ONE + dir.offset())
+      );
+    }
+
+    assert_eq!(
+      NeighborDirection::SouthEast.offset_of(UVec2::new(5, 0)),
+      None
+    );
+    assert_eq!(
+      NeighborDirection::NorthWest.offset_of(UVec2::new(0, 5)),
+      None
+    );
+  }
+
+  #[test]
+  fn bits_of_every_direction() {
+    for (i, dir) in NeighborDirection::CLOCKWISE_MOORE.into_iter().enumerate() {
+      assert_eq!(dir.bit(), 1 << i);
+
+      let moore = MooreNeighbors::from_bits(dir.bit());
+      for other in NeighborDirection::CLOCKWISE_MOORE {
+        assert_eq!(moore.get(other), Some(&(other == dir)));
       }
+      assert_eq!(moore.to_bits(), dir.bit());
+
+      let cardinal = Neighbors::from_bits(dir.bit());
+      let expected = if dir.is_diagonal() { 0 } else { dir.bit() };
+      assert_eq!(cardinal.to_bits(), expected);
+      assert_eq!(cardinal.len(), 4);
+      assert_eq!(moore.cardinal().to_bits(), expected);
+    }
+  }
+
+  #[test]
+  fn moore_bits_round_trip() {
+    for bits in 0..=u8::MAX {
+      assert_eq!(MooreNeighbors::from_bits(bits).to_bits(), bits);
+    }
+  }
+
+  #[test]
+  fn cardinal_bits_match_moore_bits() {
+    let neighbors = MooreNeighbors::from_fn(IVec2::ZERO, |pos| Some(pos.x > 0));
+
+    assert_eq!(
+      neighbors.to_bits(),
+      NeighborDirection::NorthEast.bit()
+        | NeighborDirection::East.bit()
+        | NeighborDirection::SouthEast.bit()
+    );
+    assert_eq!(
+      neighbors.cardinal().to_bits(),
+      NeighborDirection::East.bit()
+    );
+    assert_eq!(Neighbors::from_bits(0xff).to_bits(), 0b0101_0101);
+  }
+
+  #[test]
+  fn world_neighbors_cross_chunk_borders() {
+    let mut world = World::new();
+    let mut chunks = Chunks::default();
+    let conf = TileMapConf::default();
+
+    // Every chunk around the origin except the one south west of it.
+    for id in [IVec2::new(0, 0), IVec2::new(-1, 0), IVec2::new(0, -1)].map(ChunkId) {
+      chunks.insert(id, world.spawn((id, ChunkTiles::new(&conf))).id());
+    }
+
+    world.insert_resource(chunks);
+    world.insert_resource(conf);
+
+    let mut state = SystemState::<TileMap>::new(&mut world);
+    let mut map = state.get_mut(&mut world);
+
+    for (pos, tile) in [
+      (IVec2::new(0, 1), Tiles::Grass),
+      (IVec2::new(-1, 0), Tiles::WaterDeep),
+      (IVec2::new(0, -1), Tiles::Beach),
+      (IVec2::new(-1, 1), Tiles::WaterDeep),
+      (IVec2::new(1, -1), Tiles::Beach),
+    ] {
+      assert!(map.set(TileLayer::Ground, TilePos(pos), tile));
     }
+    state.apply(&mut world);
+
+    let map = state.get_mut(&mut world);
+
+    let neighbors = Neighbors::from_world_tiles(TilePos::new(0, 0), &map);
+    assert_eq!(neighbors.north, Some(Tiles::Grass));
+    assert_eq!(neighbors.east, Some(Tiles::Void));
+    assert_eq!(neighbors.south, Some(Tiles::Beach));
+    assert_eq!(neighbors.west, Some(Tiles::WaterDeep));
+
+    let moore = MooreNeighbors::from_world_tiles(TilePos::new(0, 0), &map);
+    assert_eq!(moore.north_west, Some(Tiles::WaterDeep));
+    assert_eq!(moore.north_east, Some(Tiles::Void));
+    assert_eq!(moore.south_east, Some(Tiles::Beach));
+    assert_eq!(moore.south_west, None);
+
+    // The west neighbor of a tile in the south west chunk isn't loaded either.
+    let neighbors = Neighbors::from_world_tiles(TilePos::new(0, -1), &map);
+    assert_eq!(neighbors.north, Some(Tiles::Void));
+    assert_eq!(neighbors.west, None);
+    assert_eq!(neighbors.len(), 3);
   }
 }
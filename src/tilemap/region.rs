@@ -0,0 +1,463 @@
+//! Region files grouping the encoded chunks of a [REGION_CHUNKS_SQ] square on disk.
+//!
+//! A region file starts with a header of the magic bytes, version and an entry for every chunk
+//! in the region holding the offset, length and checksum of its data. Chunk data is only ever
+//! appended, and the entry of a chunk is updated once its data is synced, so a crash while
+//! writing leaves the previous data of the chunk in place. Overwritten data is left behind as
+//! garbage until the file is compacted.
+use super::chunk::ChunkId;
+use super::codec::invalid_data;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The sqrt of the number of chunks within a region.
+pub const REGION_CHUNKS_SQ: usize = 32;
+/// The total number of chunks within a region.
+pub const REGION_CHUNKS: usize = REGION_CHUNKS_SQ * REGION_CHUNKS_SQ;
+
+/// The bytes every region file starts with.
+const MAGIC: &[u8; 4] = b"UFRG";
+/// The version of the region file format.
+const VERSION: u32 = 1;
+/// The size of the entry of a chunk in the header.
+const ENTRY_SIZE: u64 = 16;
+/// The size of the header of a region file.
+const HEADER_SIZE: u64 = 8 + ENTRY_SIZE * REGION_CHUNKS as u64;
+
+#[derive(Debug, Clone, Copy, Deref, DerefMut, PartialEq, Eq, Hash)]
+pub struct RegionId(pub IVec2);
+
+impl RegionId {
+  /// Gets the region containing the given chunk.
+  pub fn from_chunk(chunk: ChunkId) -> Self {
+    Self(chunk.div_euclid(IVec2::splat(REGION_CHUNKS_SQ as _)))
+  }
+
+  /// Gets the index of the entry of the given chunk within its region.
+  fn entry(chunk: ChunkId) -> usize {
+    let local = chunk
+      .rem_euclid(IVec2::splat(REGION_CHUNKS_SQ as _))
+      .as_uvec2();
+
+    local.x as usize * REGION_CHUNKS_SQ + local.y as usize
+  }
+
+  fn file_name(self) -> String {
+    format!("r.{}.{}.ufr", self.x, self.y)
+  }
+}
+
+/// Where the data of a chunk lives within a region file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+  offset: u32,
+  len: u32,
+  checksum: u32,
+}
+
+impl Entry {
+  fn to_bytes(self) -> [u8; ENTRY_SIZE as usize] {
+    let mut bytes = [0; ENTRY_SIZE as usize];
+    bytes[0..4].copy_from_slice(&self.offset.to_le_bytes());
+    bytes[4..8].copy_from_slice(&self.len.to_le_bytes());
+    bytes[8..12].copy_from_slice(&self.checksum.to_le_bytes());
+
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Self {
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+    Self {
+      offset: u32_at(0),
+      len: u32_at(4),
+      checksum: u32_at(8),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+}
+
+/// A region file open for reading and writing chunks.
+pub struct RegionFile {
+  path: PathBuf,
+  file: File,
+  entries: Box<[Entry; REGION_CHUNKS]>,
+  len: u64,
+}
+
+impl RegionFile {
+  /// Opens the region file at the given path, creating it when it doesn't exist.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let path = path.as_ref().to_path_buf();
+    let mut file = match OpenOptions::new().read(true).write(true).open(&path) {
+      Ok(file) => file,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Self::create(path),
+      Err(err) => return Err(err),
+    };
+
+    let len = file.metadata()?.len();
+
+    let mut header = vec![0; HEADER_SIZE as usize];
+    file.read_exact(&mut header)?;
+
+    if &header[0..4] != MAGIC {
+      return Err(invalid_data(format!(
+        "{} is not a region file",
+        path.display()
+      )));
+    }
+
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != VERSION {
+      return Err(invalid_data(format!(
+        "unsupported region file version {version}"
+      )));
+    }
+
+    let mut entries = Box::new([Entry::default(); REGION_CHUNKS]);
+    for (i, entry) in entries.iter_mut().enumerate() {
+      let at = 8 + i * ENTRY_SIZE as usize;
+
+      *entry = Entry::from_bytes(&header[at..at + ENTRY_SIZE as usize]);
+    }
+
+    Ok(Self {
+      path,
+      file,
+      entries,
+      len,
+    })
+  }
+
+  /// Creates an empty region file at the given path.
+  ///
+  /// The header is written next to the region file and renamed into place once synced, like
+  /// [RegionFile::compact], so a crash can't leave a region file without a header.
+  fn create(path: PathBuf) -> io::Result<Self> {
+    let tmp = path.with_extension("new");
+
+    let mut region = Self {
+      path: tmp.clone(),
+      file: File::create(&tmp)?,
+      entries: Box::new([Entry::default(); REGION_CHUNKS]),
+      len: HEADER_SIZE,
+    };
+    region.write_header()?;
+    region.file.sync_all()?;
+
+    fs::rename(&tmp, &path)?;
+    region.path = path;
+    region.file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .open(&region.path)?;
+
+    Ok(region)
+  }
+
+  /// Reads the data of the given chunk if it was written to the region.
+  pub fn read(&mut self, chunk: ChunkId) -> io::Result<Option<Vec<u8>>> {
+    let entry = self.entries[RegionId::entry(chunk)];
+    if entry.is_empty() {
+      return Ok(None);
+    }
+
+    if entry.offset as u64 + entry.len as u64 > self.len {
+      return Err(invalid_data(format!(
+        "chunk {chunk:?} is outside of its region file"
+      )));
+    }
+
+    let mut bytes = vec![0; entry.len as usize];
+    self.file.seek(SeekFrom::Start(entry.offset as _))?;
+    self.file.read_exact(&mut bytes)?;
+
+    if checksum(&bytes) != entry.checksum {
+      return Err(invalid_data(format!("chunk {chunk:?} failed its checksum")));
+    }
+
+    Ok(Some(bytes))
+  }
+
+  /// Writes the data of the given chunk replacing its previous data.
+  pub fn write(&mut self, chunk: ChunkId, bytes: &[u8]) -> io::Result<()> {
+    let offset = u32::try_from(self.len)
+      .map_err(|_| io::Error::other("region file is full"))?;
+
+    self.file.seek(SeekFrom::Start(self.len))?;
+    self.file.write_all(bytes)?;
+    self.file.sync_data()?;
+    self.len += bytes.len() as u64;
+
+    let i = RegionId::entry(chunk);
+    self.entries[i] = Entry {
+      offset,
+      len: bytes.len() as _,
+      checksum: checksum(bytes),
+    };
+
+    self.write_entry(i)?;
+    self.file.sync_data()
+  }
+
+  /// Gets the number of bytes taken by overwritten chunk data.
+  pub fn garbage(&self) -> u64 {
+    let live = self
+      .entries
+      .iter()
+      .map(|entry| entry.len as u64)
+      .sum::<u64>();
+
+    self.len - HEADER_SIZE - live
+  }
+
+  /// Rewrites the region file without overwritten chunk data.
+  ///
+  /// The compacted file is written next to the region file and renamed over it once synced, so a
+  /// crash while compacting leaves the region file as it was.
+  pub fn compact(mut self) -> io::Result<Self> {
+    let tmp = self.path.with_extension("tmp");
+    _ = fs::remove_file(&tmp);
+
+    let mut compacted = RegionFile::open(&tmp)?;
+
+    for i in 0..REGION_CHUNKS {
+      let entry = self.entries[i];
+      if entry.is_empty() {
+        continue;
+      }
+
+      let mut bytes = vec![0; entry.len as usize];
+      self.file.seek(SeekFrom::Start(entry.offset as _))?;
+      self.file.read_exact(&mut bytes)?;
+
+      compacted.file.seek(SeekFrom::Start(compacted.len))?;
+      compacted.file.write_all(&bytes)?;
+      compacted.entries[i] = Entry {
+        offset: compacted.len as _,
+        ..entry
+      };
+      compacted.len += bytes.len() as u64;
+    }
+
+    compacted.write_header()?;
+    compacted.file.sync_all()?;
+
+    fs::rename(&tmp, &self.path)?;
+    compacted.path = self.path;
+
+    Ok(compacted)
+  }
+
+  fn write_header(&mut self) -> io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    for entry in self.entries.iter() {
+      header.extend_from_slice(&entry.to_bytes());
+    }
+
+    self.file.seek(SeekFrom::Start(0))?;
+    self.file.write_all(&header)?;
+    self.file.sync_data()
+  }
+
+  fn write_entry(&mut self, i: usize) -> io::Result<()> {
+    self.file.seek(SeekFrom::Start(8 + i as u64 * ENTRY_SIZE))?;
+    self.file.write_all(&self.entries[i].to_bytes())
+  }
+}
+
+/// A directory of region files opened as they're needed.
+pub struct RegionDir {
+  path: PathBuf,
+  regions: HashMap<RegionId, RegionFile, fxhash::FxBuildHasher>,
+}
+
+impl RegionDir {
+  /// Creates a [RegionDir] in the given directory, creating the directory if needed.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    fs::create_dir_all(&path)?;
+
+    Ok(Self {
+      path: path.as_ref().canonicalize()?,
+      regions: Default::default(),
+    })
+  }
+
+  /// Gets the canonical path of the directory.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Reads the data of the given chunk if it was written to its region.
+  pub fn read(&mut self, chunk: ChunkId) -> io::Result<Option<Vec<u8>>> {
+    let id = RegionId::from_chunk(chunk);
+    if !self.regions.contains_key(&id) && !self.path.join(id.file_name()).exists() {
+      return Ok(None);
+    }
+
+    self.region(id)?.read(chunk)
+  }
+
+  /// Writes the data of the given chunk to its region.
+  pub fn write(&mut self, chunk: ChunkId, bytes: &[u8]) -> io::Result<()> {
+    self
+      .region(RegionId::from_chunk(chunk))?
+      .write(chunk, bytes)
+  }
+
+  /// Compacts the open region files that are mostly overwritten chunk data.
+  pub fn compact(&mut self) -> io::Result<()> {
+    for id in self.regions.keys().copied().collect::<Vec<_>>() {
+      let region = &self.regions[&id];
+      if region.garbage() < region.len / 2 {
+        continue;
+      }
+
+      let region = self.regions.remove(&id).unwrap();
+      self.regions.insert(id, region.compact()?);
+    }
+
+    Ok(())
+  }
+
+  fn region(&mut self, id: RegionId) -> io::Result<&mut RegionFile> {
+    if !self.regions.contains_key(&id) {
+      let region = RegionFile::open(self.path.join(id.file_name()))?;
+      self.regions.insert(id, region);
+    }
+
+    Ok(self.regions.get_mut(&id).unwrap())
+  }
+}
+
+/// Gets the FNV-1a hash of the given bytes.
+fn checksum(bytes: &[u8]) -> u32 {
+  bytes.iter().fold(0x811c9dc5, |hash, byte| {
+    (hash ^ *byte as u32).wrapping_mul(0x01000193)
+  })
+}
+
+/// Gets a path in the temp directory unique to this test run, removing what was left there.
+#[cfg(test)]
+pub(super) fn temp_dir(name: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("ufsg-{}-{name}", std::process::id()));
+  _ = fs::remove_dir_all(&path);
+
+  path
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn random_access() {
+    let dir = temp_dir("region-random-access");
+    let mut regions = RegionDir::open(&dir).unwrap();
+
+    let chunks = [
+      IVec2::new(0, 0),
+      IVec2::new(31, 2),
+      IVec2::new(-1, -33),
+      IVec2::new(40, 7),
+    ];
+    for (i, chunk) in chunks.into_iter().enumerate() {
+      regions
+        .write(ChunkId(chunk), &vec![i as u8; i + 1])
+        .unwrap();
+    }
+
+    regions.write(ChunkId(chunks[1]), b"overwritten").unwrap();
+    assert!(regions.read(ChunkId(IVec2::new(1, 0))).unwrap().is_none());
+    assert!(regions
+      .read(ChunkId(IVec2::new(-100, 0)))
+      .unwrap()
+      .is_none());
+
+    // Reopen the regions to read them back from disk.
+    let mut regions = RegionDir::open(&dir).unwrap();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+      let expected = match i {
+        1 => b"overwritten".to_vec(),
+        _ => vec![i as u8; i + 1],
+      };
+
+      assert_eq!(regions.read(ChunkId(chunk)).unwrap(), Some(expected));
+    }
+
+    _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn compaction_keeps_chunks() {
+    let dir = temp_dir("region-compaction");
+    let mut region = RegionFile::open(dir.with_extension("ufr")).unwrap();
+
+    for i in 0..8u8 {
+      region.write(ChunkId(IVec2::new(0, 0)), &[i; 64]).unwrap();
+    }
+    region.write(ChunkId(IVec2::new(5, 9)), &[42; 10]).unwrap();
+
+    assert_eq!(region.garbage(), 7 * 64);
+
+    let region = region.compact().unwrap();
+    assert_eq!(region.garbage(), 0);
+    assert_eq!(region.len, HEADER_SIZE + 64 + 10);
+
+    let mut region = RegionFile::open(dir.with_extension("ufr")).unwrap();
+    assert_eq!(
+      region.read(ChunkId(IVec2::new(0, 0))).unwrap(),
+      Some(vec![7; 64])
+    );
+    assert_eq!(
+      region.read(ChunkId(IVec2::new(5, 9))).unwrap(),
+      Some(vec![42; 10])
+    );
+
+    _ = fs::remove_file(dir.with_extension("ufr"));
+  }
+
+  #[test]
+  fn creates_region_files_with_a_header() {
+    let path = temp_dir("region-create").with_extension("ufr");
+    let region = RegionFile::open(&path).unwrap();
+
+    assert_eq!(region.len, HEADER_SIZE);
+    assert_eq!(fs::metadata(&path).unwrap().len(), HEADER_SIZE);
+    assert!(!path.with_extension("new").exists());
+
+    drop(region);
+    assert!(RegionFile::open(&path)
+      .unwrap()
+      .entries
+      .iter()
+      .all(Entry::is_empty));
+
+    _ = fs::remove_file(&path);
+  }
+
+  #[test]
+  fn detects_torn_writes() {
+    let path = temp_dir("region-torn").with_extension("ufr");
+    let mut region = RegionFile::open(&path).unwrap();
+    region.write(ChunkId(IVec2::ZERO), &[1, 2, 3, 4]).unwrap();
+
+    // Corrupt the chunk data as if the write never made it to disk.
+    region.file.seek(SeekFrom::Start(HEADER_SIZE)).unwrap();
+    region.file.write_all(&[0, 0]).unwrap();
+
+    let err = region.read(ChunkId(IVec2::ZERO)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    _ = fs::remove_file(&path);
+  }
+}
@@ -1,33 +1,137 @@
-use super::chunk::ChunkTiles;
-use super::CHUNK_TILES;
-use crate::sprites::Tiles;
+use super::chunk::{ChunkTiles, TileLayer};
+use super::TileMapConf;
+use crate::sprites::{TileAtlas, Tiles};
 use bevy::prelude::*;
-use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::render::render_resource::encase::StorageBuffer;
+use bevy::render::render_resource::{
+  AsBindGroup, Buffer, BufferInitDescriptor, BufferUsages, ShaderRef,
+};
+use bevy::render::renderer::RenderDevice;
 use bevy::sprite::Material2d;
 
-/// A material that renders a chunk of tiles.
+/// Assets shared by every chunk, created once by [setup].
+#[derive(Resource)]
+pub struct TileMapAssets {
+  /// The quad every chunk is drawn on.
+  pub mesh: Handle<Mesh>,
+  /// The tile set image.
+  pub atlas: Handle<Image>,
+  /// The `(start, len)` of the frames in `frames` of each tile index.
+  animations: Buffer,
+  /// The `(tile index, end time in ms)` of each animation frame.
+  frames: Buffer,
+}
+
+/// Creates the [TileMapAssets].
+pub fn setup(
+  mut commands: Commands,
+  mut meshes: ResMut<Assets<Mesh>>,
+  assets: Res<AssetServer>,
+  device: Res<RenderDevice>,
+  conf: Res<TileMapConf>,
+) {
+  let (animations, frames) = animation_tables();
+  let storage = |label: &str, data: &Vec<UVec2>| {
+    let mut buffer = StorageBuffer::new(Vec::new());
+    buffer.write(data).unwrap();
+
+    device.create_buffer_with_data(&BufferInitDescriptor {
+      label: Some(label),
+      usage: BufferUsages::STORAGE,
+      contents: buffer.as_ref(),
+    })
+  };
+
+  commands.insert_resource(TileMapAssets {
+    mesh: meshes.add(shape::Quad::new(Vec2::splat(conf.chunk_size_sq())).into()),
+    atlas: assets.load(Tiles::path()),
+    animations: storage("tile_animations", &animations),
+    frames: storage("tile_animation_frames", &frames),
+  });
+}
+
+/// The tile index of empty tiles on the layers above the ground.
+const EMPTY_TILE: u32 = u32::MAX;
+
+/// A material that renders a chunk of tiles, compositing its layers in a single draw.
+///
+/// Only the tiles are owned by the material, the image and animation tables are shared through
+/// [TileMapAssets].
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct ChunkMaterial {
   #[texture(1)]
   #[sampler(2)]
   image: Handle<Image>,
+  /// The tile indices of each layer in draw order.
   #[storage(3)]
-  tiles: [u32; CHUNK_TILES],
+  tiles: Vec<u32>,
   #[uniform(4)]
-  tile_size: u32,
+  atlas: TileAtlas,
+  /// The sqrt of the number of tiles, so the shader doesn't have to derive it from `tiles`.
+  #[uniform(7)]
+  chunk_tiles_sq: u32,
+  #[storage(5, read_only, buffer)]
+  animations: Buffer,
+  #[storage(6, read_only, buffer)]
+  frames: Buffer,
 }
 
 impl ChunkMaterial {
-  /// Creates a [ChunkMaterial] from the given image.
-  pub fn from_image(image: Handle<Image>) -> Self {
+  /// Creates a [ChunkMaterial] of void tiles using the shared [TileMapAssets].
+  pub fn new(assets: &TileMapAssets, conf: &TileMapConf) -> Self {
     Self {
-      image,
-      tiles: [Tiles::Void as _; CHUNK_TILES],
-      tile_size: 8,
+      image: assets.atlas.clone(),
+      tiles: TileLayer::ALL
+        .iter()
+        .flat_map(|layer| vec![tile_index(*layer, Tiles::Void); conf.chunk_tiles()])
+        .collect(),
+      atlas: Tiles::ATLAS,
+      chunk_tiles_sq: conf.chunk_tiles_sq,
+      animations: assets.animations.clone(),
+      frames: assets.frames.clone(),
     }
   }
 }
 
+/// Gets the animation tables of [TileMapAssets] from [Tiles::ANIMATIONS].
+///
+/// Frames store the time their animation ends at rather than their duration so the shader can
+/// find the current frame with a single pass.
+fn animation_tables() -> (Vec<UVec2>, Vec<UVec2>) {
+  let len = Tiles::ALL
+    .iter()
+    .map(|tile| tile.index() as usize + 1)
+    .max();
+  let mut animations = vec![UVec2::ZERO; len.unwrap_or(1)];
+  let mut frames = Vec::new();
+
+  for (tile, tile_frames) in Tiles::ANIMATIONS {
+    animations[tile.index() as usize] = UVec2::new(frames.len() as _, tile_frames.len() as _);
+
+    let mut end = 0;
+    for (index, duration) in tile_frames.iter() {
+      end += duration;
+      frames.push(UVec2::new(*index, end));
+    }
+  }
+
+  // Storage buffers can't be empty.
+  if frames.is_empty() {
+    frames.push(UVec2::ZERO);
+  }
+
+  (animations, frames)
+}
+
+/// Gets the index the shader draws for a tile of the given layer.
+fn tile_index(layer: TileLayer, tile: Tiles) -> u32 {
+  match (layer, tile) {
+    (TileLayer::Ground, tile) => tile.index(),
+    (_, Tiles::Void) => EMPTY_TILE,
+    (_, tile) => tile.index(),
+  }
+}
+
 impl Material2d for ChunkMaterial {
   fn fragment_shader() -> ShaderRef {
     "shaders/tiles.wgsl".into()
@@ -45,8 +149,60 @@ pub fn update_material(
 
     let material = materials.get_mut(handle).unwrap();
 
-    for (from, to) in tiles.iter().zip(material.tiles.iter_mut()) {
-      *to = *from as _;
+    let layers = TileLayer::ALL.iter().flat_map(|layer| {
+      tiles
+        .layer(*layer)
+        .iter()
+        .map(|tile| tile_index(*layer, *tile))
+    });
+
+    for (from, to) in layers.zip(material.tiles.iter_mut()) {
+      *to = from;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn animation_tables_index_frames_by_tile() {
+    let (animations, frames) = animation_tables();
+
+    assert!(Tiles::ALL
+      .iter()
+      .all(|tile| (tile.index() as usize) < animations.len()));
+
+    let animated = Tiles::ANIMATIONS
+      .iter()
+      .map(|(tile, _)| tile.index())
+      .collect::<Vec<_>>();
+    for tile in Tiles::ALL {
+      if !animated.contains(&tile.index()) {
+        assert_eq!(animations[tile.index() as usize].y, 0, "{tile:?}");
+      }
     }
+
+    for (tile, tile_frames) in Tiles::ANIMATIONS {
+      let UVec2 { x: start, y: len } = animations[tile.index() as usize];
+      assert_eq!(len as usize, tile_frames.len());
+
+      let mut end = 0;
+      for (i, (index, duration)) in tile_frames.iter().enumerate() {
+        end += duration;
+        assert_eq!(frames[start as usize + i], UVec2::new(*index, end));
+      }
+    }
+
+    let UVec2 { x: start, y: len } = animations[Tiles::WaterShallow.index() as usize];
+    assert_eq!(
+      frames[start as usize..(start + len) as usize],
+      [
+        UVec2::new(20, 600),
+        UVec2::new(21, 900),
+        UVec2::new(22, 1200)
+      ]
+    );
   }
 }
@@ -0,0 +1,562 @@
+use super::chunk::{ChunkDirty, ChunkGenerating, ChunkId, ChunkTiles, Chunks};
+use super::codec::invalid_data;
+use super::data::TileDataRegistry;
+use super::generate::{self, GenerateConf};
+use super::region::RegionDir;
+use super::TileMapConf;
+use bevy::ecs::world::EntityRef;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The directory the world is saved to.
+pub const SAVE_PATH: &str = "saves/world";
+
+/// The name of the world file within a save.
+const WORLD_FILE: &str = "world.ufsg";
+/// The name of the directory of region files within a save.
+const REGIONS_DIR: &str = "regions";
+/// The name of the directory region files are written to before they replace [REGIONS_DIR].
+const NEW_REGIONS_DIR: &str = "regions.new";
+/// The name of the directory replaced region files are kept in until the world file is replaced.
+const OLD_REGIONS_DIR: &str = "regions.old";
+/// The bytes every world file starts with.
+const MAGIC: &[u8; 4] = b"UFSG";
+/// The version of the world file format.
+const VERSION: u16 = 9;
+
+/// The tiles and encoded data layers of a chunk restored from a [ChunkStore].
+pub struct StoredChunk {
+  pub tiles: ChunkTiles,
+  /// The encoded data layers by name, see [super::data].
+  pub data: HashMap<String, Vec<u8>>,
+}
+
+/// Chunks kept around while they're unloaded, restored in place of generated chunks.
+///
+/// Only chunks that differ from what [generate::generate] would produce need to be stored, so
+/// chunks are stored when they are unloaded with [ChunkDirty]. Stored chunks are kept in memory
+/// until they're written to the region files of a save, after which they're read back from disk.
+#[derive(Default, Resource)]
+pub struct ChunkStore {
+  chunks: HashMap<ChunkId, Vec<u8>, fxhash::FxBuildHasher>,
+  regions: Option<RegionDir>,
+}
+
+impl ChunkStore {
+  /// Creates a [ChunkStore] reading chunks from the save in the given directory.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let path = path.as_ref();
+    let regions = path.join(REGIONS_DIR);
+
+    // A crash while swapping in new region files leaves the previous ones set aside.
+    let old = path.join(OLD_REGIONS_DIR);
+    if !regions.exists() && old.exists() {
+      fs::rename(&old, &regions)?;
+    }
+
+    Ok(Self {
+      chunks: Default::default(),
+      regions: Some(RegionDir::open(regions)?),
+    })
+  }
+
+  /// Stores the tiles and encoded data layers of the given chunk replacing what was previously
+  /// stored.
+  pub fn insert(&mut self, id: ChunkId, tiles: &ChunkTiles, data: &[(&str, Vec<u8>)]) {
+    self.chunks.insert(id, encode_chunk(tiles, data));
+  }
+
+  /// Stores the given chunk entity with the data layers of the registry.
+  pub fn insert_entity(&mut self, registry: &TileDataRegistry, entity: &EntityRef) {
+    let (Some(id), Some(tiles)) = (entity.get::<ChunkId>(), entity.get::<ChunkTiles>()) else {
+      return;
+    };
+
+    self.insert(*id, tiles, &registry.encode(entity));
+  }
+
+  /// Gets the stored tiles and data layers of the given chunk.
+  pub fn get(&mut self, id: ChunkId, conf: &TileMapConf) -> Option<io::Result<StoredChunk>> {
+    if let Some(bytes) = self.chunks.get(&id) {
+      return Some(decode_chunk(bytes, conf));
+    }
+
+    match self.regions.as_mut()?.read(id) {
+      Ok(bytes) => bytes.map(|bytes| decode_chunk(&bytes, conf)),
+      Err(err) => Some(Err(err)),
+    }
+  }
+
+  /// Writes the stored chunks to the region files of the save in the given directory.
+  ///
+  /// A store that wasn't opened from the save starts it over with new region files, otherwise
+  /// chunks from another world would be mixed in. They're written to [NEW_REGIONS_DIR] and
+  /// replace the region files of the save once complete, which are set aside in
+  /// [OLD_REGIONS_DIR] until [save_world] replaces the world file.
+  pub fn save<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
+    let path = path.as_ref().canonicalize()?;
+    let regions_path = path.join(REGIONS_DIR);
+
+    let is_open = matches!(&self.regions, Some(regions) if regions.path() == regions_path);
+    if !is_open {
+      let new = path.join(NEW_REGIONS_DIR);
+      if new.exists() {
+        fs::remove_dir_all(&new)?;
+      }
+      fs::create_dir_all(&new)?;
+
+      // Carry over the chunks of the save the store was opened from.
+      if let Some(previous) = &self.regions {
+        for entry in fs::read_dir(previous.path())? {
+          let entry = entry?;
+          fs::copy(entry.path(), new.join(entry.file_name()))?;
+        }
+      }
+
+      self.regions = Some(RegionDir::open(&new)?);
+    }
+
+    let regions = self.regions.as_mut().unwrap();
+    for (id, bytes) in &self.chunks {
+      regions.write(*id, bytes)?;
+    }
+
+    regions.compact()?;
+
+    if !is_open {
+      self.regions = None;
+
+      let old = path.join(OLD_REGIONS_DIR);
+      if old.exists() {
+        fs::remove_dir_all(&old)?;
+      }
+      if regions_path.exists() {
+        fs::rename(&regions_path, &old)?;
+      }
+      fs::rename(path.join(NEW_REGIONS_DIR), &regions_path)?;
+
+      self.regions = Some(RegionDir::open(&regions_path)?);
+    }
+
+    let len = self.chunks.len();
+    self.chunks.clear();
+
+    Ok(len)
+  }
+}
+
+/// Encodes the tiles of a chunk followed by its data layers.
+///
+/// Data layers are their count as a `u8` followed by the length of the name of each layer as a
+/// `u8`, its name, the length of its bytes as a little endian `u32` and its bytes.
+fn encode_chunk(tiles: &ChunkTiles, data: &[(&str, Vec<u8>)]) -> Vec<u8> {
+  let mut bytes = tiles.encode();
+
+  bytes.push(data.len() as u8);
+  for (name, data) in data {
+    bytes.push(name.len() as u8);
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+  }
+
+  bytes
+}
+
+/// Decodes a chunk encoded with [encode_chunk].
+fn decode_chunk(mut bytes: &[u8], conf: &TileMapConf) -> io::Result<StoredChunk> {
+  let tiles = ChunkTiles::decode(&mut bytes, conf)?;
+
+  let [len] = read_bytes(&mut bytes)?;
+  let mut data = HashMap::new();
+
+  for _ in 0..len {
+    let [name_len] = read_bytes(&mut bytes)?;
+    let mut name = vec![0; name_len as usize];
+    bytes.read_exact(&mut name)?;
+    let name = String::from_utf8(name).map_err(invalid_data)?;
+
+    let len = u32::from_le_bytes(read_bytes(&mut bytes)?);
+    let mut layer = vec![0; len as usize];
+    bytes.read_exact(&mut layer)?;
+
+    data.insert(name, layer);
+  }
+
+  Ok(StoredChunk { tiles, data })
+}
+
+/// Saves the world to the given directory as a world file and the region files of `store`.
+///
+/// The world file is written next to the save and only renamed into place once the region files
+/// are, so a crash can't leave a partially written world file or one without its region files.
+pub fn save_world<P: AsRef<Path>>(
+  path: P,
+  conf: &GenerateConf,
+  map: &TileMapConf,
+  store: &mut ChunkStore,
+) -> io::Result<usize> {
+  let path = path.as_ref();
+  fs::create_dir_all(path)?;
+
+  let tmp = path.join(WORLD_FILE).with_extension("tmp");
+  let mut file = BufWriter::new(File::create(&tmp)?);
+  write_world(&mut file, conf, map)?;
+  file.into_inner()?.sync_all()?;
+
+  let len = store.save(path)?;
+  fs::rename(&tmp, path.join(WORLD_FILE))?;
+
+  let old = path.join(OLD_REGIONS_DIR);
+  if old.exists() {
+    fs::remove_dir_all(&old)?;
+  }
+
+  Ok(len)
+}
+
+/// Loads the world saved to the given directory by [save_world].
+///
+/// Stored chunks can only be decoded with the chunk dimension they were saved with, so worlds
+/// saved with a different dimension than `map` are rejected.
+pub fn load_world<P: AsRef<Path>>(
+  path: P,
+  map: &TileMapConf,
+) -> io::Result<(GenerateConf, ChunkStore)> {
+  let path = path.as_ref();
+  let file = File::open(path.join(WORLD_FILE))?;
+  let (conf, chunk_tiles_sq) = read_world(BufReader::new(file))?;
+
+  if chunk_tiles_sq != map.chunk_tiles_sq {
+    return Err(invalid_data(format!(
+      "world has chunks of {chunk_tiles_sq} tiles, expected {}",
+      map.chunk_tiles_sq
+    )));
+  }
+
+  Ok((conf, ChunkStore::open(path)?))
+}
+
+/// Writes a world file with the generation config and chunk dimension.
+///
+/// A world file is the magic bytes, version, chunk dimension, seed, continent scale, biome scale,
+/// temperature and moisture, all little endian.
+pub fn write_world<W: Write>(mut w: W, conf: &GenerateConf, map: &TileMapConf) -> io::Result<()> {
+  w.write_all(MAGIC)?;
+  w.write_all(&VERSION.to_le_bytes())?;
+  w.write_all(&map.chunk_tiles_sq.to_le_bytes())?;
+  w.write_all(&conf.seed.to_le_bytes())?;
+  w.write_all(&conf.continent_scale.to_le_bytes())?;
+  w.write_all(&conf.biome_scale.to_le_bytes())?;
+  w.write_all(&conf.temperature.to_le_bytes())?;
+  w.write_all(&conf.moisture.to_le_bytes())?;
+
+  w.flush()
+}
+
+/// Reads a world file written by [write_world], returning the generation config and chunk
+/// dimension.
+pub fn read_world<R: Read>(mut r: R) -> io::Result<(GenerateConf, u32)> {
+  let mut magic = [0; 4];
+  r.read_exact(&mut magic)?;
+  if &magic != MAGIC {
+    return Err(invalid_data("not a world file"));
+  }
+
+  let version = u16::from_le_bytes(read_bytes(&mut r)?);
+  if version != VERSION {
+    return Err(invalid_data(format!(
+      "unsupported world file version {version}"
+    )));
+  }
+
+  let chunk_tiles_sq = u32::from_le_bytes(read_bytes(&mut r)?);
+  let conf = GenerateConf {
+    seed: u32::from_le_bytes(read_bytes(&mut r)?),
+    continent_scale: f64::from_le_bytes(read_bytes(&mut r)?),
+    biome_scale: f64::from_le_bytes(read_bytes(&mut r)?),
+    temperature: f64::from_le_bytes(read_bytes(&mut r)?),
+    moisture: f64::from_le_bytes(read_bytes(&mut r)?),
+  };
+
+  Ok((conf, chunk_tiles_sq))
+}
+
+fn read_bytes<const N: usize, R: Read>(r: &mut R) -> io::Result<[u8; N]> {
+  let mut bytes = [0; N];
+  r.read_exact(&mut bytes)?;
+
+  Ok(bytes)
+}
+
+/// Saves the edited chunks along with the stored chunks to [SAVE_PATH].
+#[tracing::instrument(skip_all)]
+pub fn save(
+  conf: Res<GenerateConf>,
+  map: Res<TileMapConf>,
+  registry: Res<TileDataRegistry>,
+  mut store: ResMut<ChunkStore>,
+  query: Query<EntityRef, (With<ChunkDirty>, Without<ChunkGenerating>)>,
+) {
+  for entity in &query {
+    store.insert_entity(&registry, &entity);
+  }
+
+  match save_world(SAVE_PATH, &conf, &map, &mut store) {
+    Ok(len) => info!("saved {len} chunks to {SAVE_PATH}"),
+    Err(err) => error!("failed to save world to {SAVE_PATH}: {err}"),
+  }
+}
+
+/// Loads the world from [SAVE_PATH] replacing the tiles and data layers of the loaded chunks.
+#[tracing::instrument(skip_all)]
+pub fn load(
+  map: Res<TileMapConf>,
+  registry: Res<TileDataRegistry>,
+  chunks: Res<Chunks>,
+  mut conf: ResMut<GenerateConf>,
+  mut store: ResMut<ChunkStore>,
+  mut commands: Commands,
+  mut query: Query<(&ChunkId, &mut ChunkTiles, &mut Visibility)>,
+) {
+  let (loaded_conf, loaded) = match load_world(SAVE_PATH, &map) {
+    Ok(world) => world,
+    Err(err) => {
+      error!("failed to load world from {SAVE_PATH}: {err}");
+      return;
+    }
+  };
+
+  *conf = loaded_conf;
+  *store = loaded;
+
+  for entity in chunks.values() {
+    let Ok((id, mut tiles, mut visibility)) = query.get_mut(*entity) else {
+      continue;
+    };
+
+    // The loaded tiles are either stored or generated so unloading them can't lose edits, and
+    // pending generation would overwrite them with tiles of the previous world.
+    let mut entity = commands.entity(*entity);
+    entity.remove::<(ChunkDirty, ChunkGenerating)>();
+    *visibility = Visibility::Inherited;
+
+    let data = match store.get(*id, &map) {
+      Some(Ok(stored)) => {
+        *tiles = stored.tiles;
+        stored.data
+      }
+      _ => {
+        *tiles = ChunkTiles::new(&map);
+        generate::generate(&conf, *id, &mut tiles);
+        HashMap::new()
+      }
+    };
+
+    registry.insert(&mut entity, &data, &conf, &map, *id);
+  }
+
+  info!("loaded world from {SAVE_PATH}");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::sprites::Tiles;
+  use crate::tilemap::chunk::TileLayer;
+  use crate::tilemap::region::temp_dir;
+  use crate::tilemap::TileMapConf;
+
+  #[test]
+  fn world_round_trip() {
+    let dir = temp_dir("world-round-trip");
+    let map = TileMapConf {
+      chunk_tiles_sq: 32,
+      ..Default::default()
+    };
+    let conf = GenerateConf {
+      seed: 0xbeef,
+      continent_scale: 0.75,
+      biome_scale: 0.25,
+      temperature: 0.8,
+      moisture: 0.3,
+    };
+
+    let mut chunks = Vec::new();
+    let mut store = ChunkStore::default();
+
+    for (i, id) in [IVec2::new(0, 0), IVec2::new(-3, 7), IVec2::new(64, -40)]
+      .into_iter()
+      .enumerate()
+    {
+      let mut tiles = ChunkTiles::new(&map);
+      generate::generate(&conf, ChunkId(id), &mut tiles);
+
+      let tile = Tiles::ALL[i % Tiles::ALL.len()];
+      let pos = UVec2::new(1, map.chunk_tiles_sq - 1);
+      tiles.layer_mut(TileLayer::Ground).set(pos, tile);
+      tiles.layer_mut(TileLayer::Structure).set(pos, tile);
+
+      let data = vec![i as u8; i];
+      store.insert(ChunkId(id), &tiles, &[("data", data.clone())]);
+      chunks.push((ChunkId(id), tiles, data));
+    }
+
+    assert_eq!(
+      save_world(&dir, &conf, &map, &mut store).unwrap(),
+      chunks.len()
+    );
+    assert!(store.chunks.is_empty());
+
+    let err = load_world(&dir, &TileMapConf::default()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let (loaded_conf, mut loaded) = load_world(&dir, &map).unwrap();
+
+    assert_eq!(loaded_conf.seed, conf.seed);
+    assert_eq!(loaded_conf.continent_scale, conf.continent_scale);
+    assert_eq!(loaded_conf.biome_scale, conf.biome_scale);
+    assert_eq!(loaded_conf.temperature, conf.temperature);
+    assert_eq!(loaded_conf.moisture, conf.moisture);
+
+    for (id, tiles, data) in &chunks {
+      let stored = loaded.get(*id, &map).unwrap().unwrap();
+      assert_eq!(stored.tiles, *tiles);
+      assert_eq!(stored.data.len(), 1);
+      assert_eq!(stored.data["data"], *data);
+    }
+    assert!(loaded.get(ChunkId(IVec2::new(1, 0)), &map).is_none());
+
+    _ = fs::remove_dir_all(&dir);
+  }
+
+  /// Stores a chunk of the given tile at chunk `id` in `store`, returning its tiles.
+  fn store_chunk(store: &mut ChunkStore, map: &TileMapConf, id: IVec2, tile: Tiles) -> ChunkTiles {
+    let mut tiles = ChunkTiles::new(map);
+    tiles.layer_mut(TileLayer::Ground).set(UVec2::ZERO, tile);
+    store.insert(ChunkId(id), &tiles, &[]);
+
+    tiles
+  }
+
+  #[test]
+  fn resaves_through_other_paths() {
+    let dir = temp_dir("world-resave");
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+
+    let mut store = ChunkStore::default();
+    let first = store_chunk(&mut store, &map, IVec2::ZERO, Tiles::Beach);
+    save_world(&dir, &conf, &map, &mut store).unwrap();
+
+    let (_, mut store) = load_world(&dir, &map).unwrap();
+    let second = store_chunk(&mut store, &map, IVec2::ONE, Tiles::Grass);
+
+    // The same save through a different path must keep the chunks of the save.
+    let other = dir.join("..").join(dir.file_name().unwrap());
+    assert_eq!(save_world(&other, &conf, &map, &mut store).unwrap(), 1);
+
+    let (_, mut loaded) = load_world(&dir, &map).unwrap();
+    for (id, tiles) in [(IVec2::ZERO, first), (IVec2::ONE, second)] {
+      assert_eq!(loaded.get(ChunkId(id), &map).unwrap().unwrap().tiles, tiles);
+    }
+
+    _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn replaces_the_regions_of_another_world() {
+    let dir = temp_dir("world-replace");
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+
+    let mut store = ChunkStore::default();
+    store_chunk(&mut store, &map, IVec2::ZERO, Tiles::Beach);
+    save_world(&dir, &conf, &map, &mut store).unwrap();
+
+    let mut store = ChunkStore::default();
+    let tiles = store_chunk(&mut store, &map, IVec2::ONE, Tiles::Grass);
+    save_world(&dir, &conf, &map, &mut store).unwrap();
+
+    assert!(!dir.join(NEW_REGIONS_DIR).exists());
+    assert!(!dir.join(OLD_REGIONS_DIR).exists());
+
+    let (_, mut loaded) = load_world(&dir, &map).unwrap();
+    assert!(loaded.get(ChunkId(IVec2::ZERO), &map).is_none());
+    assert_eq!(
+      loaded
+        .get(ChunkId(IVec2::ONE), &map)
+        .unwrap()
+        .unwrap()
+        .tiles,
+      tiles
+    );
+
+    _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn recovers_from_an_interrupted_save() {
+    let dir = temp_dir("world-interrupted");
+    let map = TileMapConf::default();
+    let conf = GenerateConf::default();
+
+    let mut store = ChunkStore::default();
+    let tiles = store_chunk(&mut store, &map, IVec2::ZERO, Tiles::Beach);
+    save_world(&dir, &conf, &map, &mut store).unwrap();
+
+    // Crash after setting the region files aside but before the new ones were renamed.
+    fs::rename(dir.join(REGIONS_DIR), dir.join(OLD_REGIONS_DIR)).unwrap();
+    fs::create_dir_all(dir.join(NEW_REGIONS_DIR)).unwrap();
+
+    let (_, mut loaded) = load_world(&dir, &map).unwrap();
+    assert_eq!(
+      loaded
+        .get(ChunkId(IVec2::ZERO), &map)
+        .unwrap()
+        .unwrap()
+        .tiles,
+      tiles
+    );
+
+    _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn rejects_unknown_files() {
+    let err = read_world(b"not a world".as_slice()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn store_restores_chunks() {
+    let map = TileMapConf::default();
+    let mut tiles = ChunkTiles::new(&map);
+    tiles
+      .layer_mut(TileLayer::Decoration)
+      .set(UVec2::new(3, 4), Tiles::WaterDeep);
+
+    let mut store = ChunkStore::default();
+    assert!(store.get(ChunkId(IVec2::ONE), &map).is_none());
+
+    store.insert(ChunkId(IVec2::ONE), &tiles, &[]);
+    let stored = store.get(ChunkId(IVec2::ONE), &map).unwrap().unwrap();
+    assert_eq!(stored.tiles, tiles);
+    assert!(stored.data.is_empty());
+  }
+
+  #[test]
+  fn rejects_truncated_data_layers() {
+    let map = TileMapConf::default();
+    let tiles = ChunkTiles::new(&map);
+
+    let mut bytes = encode_chunk(&tiles, &[("fertility", vec![1, 2, 3, 4])]);
+    bytes.pop();
+
+    let err = decode_chunk(&bytes, &map).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  }
+}